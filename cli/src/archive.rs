@@ -3,18 +3,21 @@ use std::path::Path;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
 
-use anyhow::bail;
+use anyhow::{anyhow, bail};
 use console::style;
 use dialoguer::theme::ColorfulTheme;
 use dialoguer::Select;
 use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use once_cell::sync::Lazy;
+use regex::Regex;
 use sanitize_filename::sanitize;
 use tokio::sync::mpsc;
 use tokio::task::JoinHandle;
 
 use sa_core::archiver;
-use sa_core::archiver::{fetch_topic_meta, DownloadEvent};
-use sa_core::client::create_client_with_token;
+use sa_core::archiver::{fetch_topic_meta, ArchiveOptions, DownloadEvent, TopicMeta};
+use sa_core::client::{create_client_with_token, Client};
+use sa_core::site::Site;
 
 #[derive(Debug)]
 struct TimeoutInEffect {
@@ -136,9 +139,10 @@ const PROMPTS: [(&str, [&str; 2], usize); 2] = [
 
 pub async fn archive(
     token: &str,
+    site: Site,
     topic_id: u32,
     save_to: &Path,
-    anonymous: bool,
+    options: &ArchiveOptions,
     create_subdir: Option<bool>,
 ) -> anyhow::Result<()> {
     let progress = MultiProgress::new();
@@ -146,9 +150,9 @@ pub async fn archive(
     let spinner = ProgressBar::new_spinner().with_message("Fetching metadata...");
     spinner.enable_steady_tick(Duration::from_millis(100));
 
-    let client = create_client_with_token(token, rate_limit_callback(progress.clone())).await?;
+    let client = connect(token, site, &progress).await?;
     let topic_meta = fetch_topic_meta(&client, topic_id).await?;
-    let filename = sanitize(format!("水源_{}", &topic_meta.title));
+    let filename = sanitize(client.site().archive_dir_name(&topic_meta.title));
 
     spinner.finish_with_message("Fetching metadata... done");
 
@@ -196,11 +200,119 @@ pub async fn archive(
         save_to.to_path_buf()
     };
 
+    archive_topic(&client, &progress, topic_meta, &save_path, options).await?;
+
+    eprintln!("{}", style("Done.").green());
+    println!("{}", save_path.display());
+    Ok(())
+}
+
+pub async fn archive_user(
+    token: &str,
+    site: Site,
+    username: &str,
+    context: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let progress = MultiProgress::new();
+    let client = connect(token, site, &progress).await?;
+
     let (tx, rx) = mpsc::channel(8);
     tokio::spawn(display_task(progress, rx));
-    archiver::archive(&client, topic_meta, &save_path, anonymous, tx).await?;
+    archiver::archive_user(&client, username, context, save_to, options, tx).await?;
 
     eprintln!("{}", style("Done.").green());
-    println!("{}", save_path.display());
+    println!(
+        "{}",
+        save_to
+            .join(archiver::user_page_filename(username))
+            .display()
+    );
+    Ok(())
+}
+
+pub async fn archive_search_posts(
+    token: &str,
+    site: Site,
+    query: &str,
+    context: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let progress = MultiProgress::new();
+    let client = connect(token, site, &progress).await?;
+
+    let (tx, rx) = mpsc::channel(8);
+    tokio::spawn(display_task(progress, rx));
+    archiver::archive_search(&client, query, context, save_to, options, tx).await?;
+
+    eprintln!("{}", style("Done.").green());
+    println!(
+        "{}",
+        save_to
+            .join(archiver::search_page_filename(query))
+            .display()
+    );
+    Ok(())
+}
+
+pub async fn archive_bookmark_posts(
+    token: &str,
+    site: Site,
+    context: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let progress = MultiProgress::new();
+    let client = connect(token, site, &progress).await?;
+
+    let (tx, rx) = mpsc::channel(8);
+    tokio::spawn(display_task(progress, rx));
+    archiver::archive_bookmarks(&client, context, save_to, options, tx).await?;
+
+    eprintln!("{}", style("Done.").green());
+    println!("{}", save_to.join(archiver::BOOKMARKS_PAGE).display());
+    Ok(())
+}
+
+/// Extract the topic id from a topic URL on given site.
+pub fn topic_id_from_url(url: &str, site: &Site) -> anyhow::Result<u32> {
+    static RE_URL: Lazy<Regex> = Lazy::new(|| Regex::new(r#"/t/[^/]+/(\d+)"#).unwrap());
+    if !site.owns_url(url) {
+        bail!(
+            "{url} is not on {}. Use --site to archive from another site.",
+            site.base_url
+        );
+    }
+    RE_URL
+        .captures(url)
+        .and_then(|caps| caps.get(1).expect("regex match").as_str().parse().ok())
+        .ok_or_else(|| anyhow!("Invalid URL."))
+}
+
+/// Create a client whose rate limiting is shown on given progress.
+pub async fn connect(token: &str, site: Site, progress: &MultiProgress) -> anyhow::Result<Client> {
+    Ok(create_client_with_token(site, token, rate_limit_callback(progress.clone())).await?)
+}
+
+/// Archive a topic into given directory, showing download progress.
+pub async fn archive_topic(
+    client: &Client,
+    progress: &MultiProgress,
+    topic_meta: TopicMeta,
+    save_path: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    if archiver::checkpoint_exists(save_path) {
+        eprintln!(
+            "{}",
+            style("An unfinished archive is found. Resuming from it.").bold()
+        );
+    }
+
+    let (tx, rx) = mpsc::channel(8);
+    tokio::spawn(display_task(progress.clone(), rx));
+    archiver::archive(client, topic_meta, save_path, options, tx).await?;
     Ok(())
 }
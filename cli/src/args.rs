@@ -1,6 +1,11 @@
 use std::path::PathBuf;
 
-use clap::{ArgGroup, Args, Parser, Subcommand};
+use clap::{ArgGroup, Args, Parser, Subcommand, ValueEnum};
+
+use sa_core::archiver::{
+    ArchiveFormat, ArchiveOptions, AttachmentOptions, Pagination, Theme, DEFAULT_PAGE_SIZE,
+};
+use sa_core::site::SHUIYUAN_BASE_URL;
 
 #[derive(Parser)]
 #[command(author, version, about)]
@@ -12,14 +17,39 @@ pub struct Opts {
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Authenticate with Shuiyuan BBS and get the API token.
+    /// Authenticate with Shuiyuan BBS (or another Discourse site) and get the API token.
     Auth {
         /// Do not open the browser automatically.
         #[clap(short, long)]
         no_open: bool,
+        /// Base URL of the Discourse site.
+        #[clap(long, default_value = SHUIYUAN_BASE_URL)]
+        site: String,
     },
     /// Archive a topic.
     Archive(Archive),
+    /// Archive all topics in a category, including its subcategories.
+    Category(CategoryArchive),
+    /// Archive all topics created and posts written by a user into a single page.
+    User(UserArchive),
+    /// Archive all topics with a tag. Topics already archived in the save path are skipped.
+    Tag(TagArchive),
+    /// Archive all topics matching a search query, or only the matching posts into a single page.
+    Search(SearchArchive),
+    /// Archive topics listed in a file concurrently. A failed topic doesn't stop the others.
+    Batch(BatchArchive),
+    /// Archive private messages in the inbox and sent box of a user.
+    Pm(PmArchive),
+    /// Archive all topics bookmarked by you, or only the bookmarked posts into a single page.
+    Bookmarks(BookmarksArchive),
+    /// Write an index page of all archives under a directory, searched recursively.
+    Library {
+        /// The directory containing archives.
+        root: PathBuf,
+        /// Directory of templates, helpers and resources overriding the embedded ones.
+        #[clap(long)]
+        template_dir: Option<PathBuf>,
+    },
 }
 
 #[derive(Args)]
@@ -34,12 +64,8 @@ pub struct Archive {
     /// The path to save the archive.
     #[clap(short, long)]
     pub save_to: PathBuf,
-    /// Whether to mask the username.
-    #[clap(short, long)]
-    pub anonymous: bool,
-    /// API token. You can get one by `auth` command.
-    #[clap(short, long)]
-    pub token: Option<String>,
+    #[command(flatten)]
+    pub common: CommonArgs,
     /// Assume yes to create subdirectories even if save path exists and is not empty.
     #[clap(long, group = "subdir")]
     pub create_subdir: bool,
@@ -47,3 +73,244 @@ pub struct Archive {
     #[clap(long, group = "subdir")]
     pub no_create_subdir: bool,
 }
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("category").args(["category_id", "url"]).required(true)))]
+pub struct CategoryArchive {
+    /// The ID of the category to archive.
+    #[clap(short = 'i', long)]
+    pub category_id: Option<usize>,
+    /// The URL of the category to archive.
+    #[clap(short, long)]
+    pub url: Option<String>,
+    /// The directory to save archives of all topics in. Each topic is saved in a subdirectory.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Args)]
+pub struct UserArchive {
+    /// The username of the user to archive.
+    pub username: String,
+    /// The directory to save the archive in. Posts are linked to topic archives in its
+    /// subdirectories, if any.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    /// Number of posts to include before and after each post of the user.
+    #[clap(long, default_value_t = 2)]
+    pub context: usize,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Args)]
+pub struct TagArchive {
+    /// The tag to archive topics of.
+    pub tag: String,
+    /// The directory to save archives of all topics in. Each topic is saved in a subdirectory.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Args)]
+pub struct SearchArchive {
+    /// The search query. Advanced search operators like `@username`, `#category`, `tags:` or
+    /// `in:title` are supported.
+    pub query: String,
+    /// The directory to save archives of all topics in. Each topic is saved in a subdirectory.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    /// Only archive the matching posts into a single page, instead of their whole topics.
+    #[clap(long)]
+    pub posts_only: bool,
+    /// Number of posts to include before and after each matching post, when archiving posts only.
+    #[clap(long, default_value_t = 2, requires = "posts_only")]
+    pub context: usize,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Args)]
+pub struct BatchArchive {
+    /// A text file with a topic ID or URL per line, or a TOML file with a `topics` array of them.
+    pub list: PathBuf,
+    /// The directory to save archives of all topics in. Each topic is saved in a subdirectory.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    /// Number of topics to archive at the same time.
+    #[clap(short, long, default_value_t = 4)]
+    pub jobs: usize,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Args)]
+#[command(group(ArgGroup::new("selection").args(["all", "topic_ids"])))]
+pub struct PmArchive {
+    /// The username whose private messages to archive, usually your own.
+    pub username: String,
+    /// The directory to save archives of all messages in. Each message is saved in a
+    /// subdirectory.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    /// Archive all messages without asking.
+    #[clap(long)]
+    pub all: bool,
+    /// IDs of the messages to archive. Asked interactively if neither this nor `--all` is given.
+    #[clap(short = 'i', long = "topic-id")]
+    pub topic_ids: Vec<u32>,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+#[derive(Args)]
+pub struct BookmarksArchive {
+    /// The directory to save archives of all topics in. Each topic is saved in a subdirectory.
+    #[clap(short, long)]
+    pub save_to: PathBuf,
+    /// Only archive the bookmarked posts into a single page, instead of their whole topics.
+    #[clap(long)]
+    pub posts_only: bool,
+    /// Number of posts to include before and after each bookmarked post, when archiving posts
+    /// only.
+    #[clap(long, default_value_t = 2, requires = "posts_only")]
+    pub context: usize,
+    #[command(flatten)]
+    pub common: CommonArgs,
+}
+
+/// Arguments shared by all archive commands.
+#[derive(Args)]
+pub struct CommonArgs {
+    /// Whether to mask the username.
+    #[clap(short, long)]
+    pub anonymous: bool,
+    /// Output format of the archive.
+    #[clap(short, long, value_enum, default_value_t = Format::Html)]
+    pub format: Format,
+    /// Resources larger than this (in MiB) are linked to the site instead of being inlined, when
+    /// the format is single-html. 0 inlines resources of any size.
+    #[clap(long, default_value_t = 20)]
+    pub inline_limit: u32,
+    /// Posts on each page, when the format is html or epub. 0 puts all posts on one page.
+    #[clap(long, default_value_t = DEFAULT_PAGE_SIZE)]
+    pub page_size: u32,
+    /// Split pages by size (in MiB, counting images and other resources) instead of post count,
+    /// when the format is html or epub.
+    #[clap(long, conflicts_with = "page_size")]
+    pub page_limit: Option<u32>,
+    /// Show posts along their reply trees instead of chronologically, when the format is html or
+    /// single-html.
+    #[clap(long)]
+    pub threaded: bool,
+    /// Directory of templates (*.hbs), script helpers (helpers/*.rhai) and resources
+    /// (resources/) overriding the embedded ones, when the format is html or single-html.
+    #[clap(long)]
+    pub template_dir: Option<PathBuf>,
+    /// Color theme the pages open with, when the format is html or single-html. Readers can
+    /// switch themes in the pages.
+    #[clap(long, value_enum, default_value_t = ThemeArg::Light)]
+    pub theme: ThemeArg,
+    /// Use stylesheets of the live site instead of the embedded ones, when the format is html or
+    /// single-html. Falls back to the embedded ones if they can't be downloaded.
+    #[clap(long)]
+    pub live_styles: bool,
+    /// Don't download attachments, e.g. PDFs and archives, but link them to the site.
+    #[clap(long)]
+    pub no_attachments: bool,
+    /// Attachments larger than this (in MiB) are linked to the site instead of being downloaded.
+    #[clap(long)]
+    pub attachment_limit: Option<u32>,
+    /// Only download attachments of these extensions, separated by commas, e.g. `pdf,docx`.
+    #[clap(long, value_delimiter = ',')]
+    pub attachment_allow: Vec<String>,
+    /// Never download attachments of these extensions, separated by commas, e.g. `exe,apk`.
+    #[clap(long, value_delimiter = ',')]
+    pub attachment_deny: Vec<String>,
+    /// Only archive downscaled previews of images, not the full-size originals they open.
+    #[clap(long)]
+    pub previews_only: bool,
+    /// API token. You can get one by `auth` command.
+    #[clap(short, long)]
+    pub token: Option<String>,
+    /// Base URL of the Discourse site.
+    #[clap(long, default_value = SHUIYUAN_BASE_URL)]
+    pub site: String,
+}
+
+impl CommonArgs {
+    pub fn archive_options(&self) -> ArchiveOptions {
+        ArchiveOptions {
+            anonymous: self.anonymous,
+            format: self.format.into(),
+            inline_limit_mib: (self.inline_limit != 0).then_some(self.inline_limit),
+            pagination: match (self.page_limit, self.page_size) {
+                (Some(mib), _) => Pagination::Size(mib),
+                (None, 0) => Pagination::Single,
+                (None, size) => Pagination::Posts(size),
+            },
+            threaded: self.threaded,
+            template_dir: self
+                .template_dir
+                .as_ref()
+                .map(|dir| dir.to_string_lossy().into_owned()),
+            theme: self.theme.into(),
+            live_styles: self.live_styles,
+            attachments: AttachmentOptions {
+                download: !self.no_attachments,
+                size_limit_mib: self.attachment_limit,
+                allow: self.attachment_allow.clone(),
+                deny: self.attachment_deny.clone(),
+            },
+            previews_only: self.previews_only,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum Format {
+    /// Paginated HTML pages.
+    Html,
+    /// A single Markdown file.
+    Markdown,
+    /// Raw JSON responses of the API.
+    Json,
+    /// A single HTML file with all resources inlined.
+    SingleHtml,
+    /// An EPUB book for e-readers.
+    Epub,
+}
+
+impl From<Format> for ArchiveFormat {
+    fn from(format: Format) -> Self {
+        match format {
+            Format::Html => Self::Html,
+            Format::Markdown => Self::Markdown,
+            Format::Json => Self::Json,
+            Format::SingleHtml => Self::SingleHtml,
+            Format::Epub => Self::Epub,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, ValueEnum)]
+pub enum ThemeArg {
+    Light,
+    Dark,
+    /// Follow the color scheme of the system.
+    Auto,
+}
+
+impl From<ThemeArg> for Theme {
+    fn from(theme: ThemeArg) -> Self {
+        match theme {
+            ThemeArg::Light => Self::Light,
+            ThemeArg::Dark => Self::Dark,
+            ThemeArg::Auto => Self::Auto,
+        }
+    }
+}
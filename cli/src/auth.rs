@@ -6,13 +6,14 @@ use tracing::warn;
 
 use sa_core::client::{oauth_url, token_from_payload};
 use sa_core::re_exports::rsa;
+use sa_core::site::Site;
 
 use crate::APP_ID;
 
-pub fn auth(no_open: bool) -> Result<()> {
+pub fn auth(no_open: bool, site: &Site) -> Result<()> {
     let key =
         rsa::RsaPrivateKey::new(&mut rand::thread_rng(), 2048).expect("generate rsa private key");
-    let url = oauth_url(&APP_ID, key.as_ref(), false);
+    let url = oauth_url(site, &APP_ID, key.as_ref(), false);
     if !no_open && webbrowser::open(&url).is_ok() {
         eprintln!("A browser window should have been opened.\n\
             Please log in and authorize the app. Then copy the authenticate key from the website and paste it here.");
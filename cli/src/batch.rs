@@ -0,0 +1,221 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::bail;
+use console::{measure_text_width, pad_str, style, Alignment};
+use futures::stream::{self, StreamExt};
+use indicatif::{MultiProgress, ProgressBar, ProgressStyle};
+use serde::Deserialize;
+use tokio::sync::mpsc;
+
+use sa_core::archiver;
+use sa_core::archiver::{
+    fetch_topic_meta, find_archives, topic_dir_name, ArchiveOptions, Templates,
+};
+use sa_core::client::Client;
+use sa_core::site::Site;
+
+use crate::archive::{connect, topic_id_from_url};
+
+/// A topic list in TOML, e.g. `topics = [12345, "https://shuiyuan.sjtu.edu.cn/t/topic/67890"]`.
+#[derive(Deserialize)]
+struct TomlList {
+    topics: Vec<TomlEntry>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TomlEntry {
+    Id(u32),
+    Url(String),
+}
+
+/// Result of archiving an entry of the list.
+struct Outcome {
+    entry: String,
+    title: Option<String>,
+    result: anyhow::Result<PathBuf>,
+}
+
+pub async fn archive_batch(
+    token: &str,
+    site: Site,
+    list: &Path,
+    save_to: &Path,
+    options: &ArchiveOptions,
+    jobs: usize,
+) -> anyhow::Result<()> {
+    let entries = read_list(list)?;
+    if entries.is_empty() {
+        bail!("No topics in {}.", list.display());
+    }
+    // Templates are compiled again for each topic, but broken ones should fail the batch early.
+    Templates::load(options.template_dir())?;
+
+    // The same topic may be listed more than once, e.g. by its id and its URL. Archiving it
+    // concurrently would write into the same directory, so only its first entry is kept.
+    let mut seen = HashSet::new();
+    let entries: Vec<(String, anyhow::Result<u32>)> = entries
+        .into_iter()
+        .map(|entry| {
+            let topic_id = topic_id_of(&entry, &site);
+            (entry, topic_id)
+        })
+        .filter(|(_, topic_id)| !matches!(topic_id, Ok(id) if !seen.insert(*id)))
+        .collect();
+
+    let progress = MultiProgress::new();
+    let client = connect(token, site, &progress).await?;
+
+    fs::create_dir_all(save_to)?;
+    let archived = find_archives(save_to);
+    let claimed = Mutex::new(HashSet::new());
+
+    let bar = progress.add(
+        ProgressBar::new(entries.len() as u64)
+            .with_style(
+                ProgressStyle::with_template(
+                    "[{elapsed_precise}] {bar:40.cyan/blue} {pos:>7}/{len:7} {msg}",
+                )
+                .unwrap()
+                .progress_chars("##-"),
+            )
+            .with_message("Archiving topics..."),
+    );
+    let outcomes: Vec<Outcome> = stream::iter(entries)
+        .map(|(entry, topic_id)| {
+            let (client, bar, archived, claimed) = (&client, &bar, &archived, &claimed);
+            async move {
+                let mut title = None;
+                let result = match topic_id {
+                    Ok(topic_id) => {
+                        archive_one(client, topic_id, save_to, archived, claimed, options, |t| {
+                            title = Some(t.to_string());
+                        })
+                        .await
+                    }
+                    Err(e) => Err(e),
+                };
+                match &result {
+                    Ok(_) => bar.println(format!("{} {entry}", style("Archived").green())),
+                    Err(e) => bar.println(format!("{} {entry}: {e}", style("Failed").red())),
+                }
+                bar.inc(1);
+                Outcome {
+                    entry,
+                    title,
+                    result,
+                }
+            }
+        })
+        .buffered(jobs.max(1))
+        .collect()
+        .await;
+    bar.finish_with_message("Archiving topics... done");
+
+    print_summary(&outcomes, save_to);
+
+    let failed = outcomes.iter().filter(|o| o.result.is_err()).count();
+    if failed > 0 {
+        bail!("{failed} of {} topics failed to archive.", outcomes.len());
+    }
+    eprintln!("{}", style("Done.").green());
+    Ok(())
+}
+
+/// Read topic ids or URLs from a TOML file, or a text file with one per line.
+///
+/// Blank lines and lines starting with `#` in text files are ignored.
+fn read_list(path: &Path) -> anyhow::Result<Vec<String>> {
+    let content = fs::read_to_string(path)?;
+    if path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
+    {
+        let list: TomlList = toml::from_str(&content)?;
+        Ok(list
+            .topics
+            .into_iter()
+            .map(|entry| match entry {
+                TomlEntry::Id(id) => id.to_string(),
+                TomlEntry::Url(url) => url,
+            })
+            .collect())
+    } else {
+        Ok(content
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(String::from)
+            .collect())
+    }
+}
+
+fn topic_id_of(entry: &str, site: &Site) -> anyhow::Result<u32> {
+    match entry.parse() {
+        Ok(topic_id) => Ok(topic_id),
+        Err(_) => topic_id_from_url(entry, site),
+    }
+}
+
+/// Archive a topic into a subdirectory of the root, reusing its existing archive if any.
+async fn archive_one(
+    client: &Client,
+    topic_id: u32,
+    root: &Path,
+    archived: &HashMap<u32, PathBuf>,
+    claimed: &Mutex<HashSet<PathBuf>>,
+    options: &ArchiveOptions,
+    on_title: impl FnOnce(&str),
+) -> anyhow::Result<PathBuf> {
+    let topic_meta = fetch_topic_meta(client, topic_id).await?;
+    on_title(&topic_meta.title);
+
+    let dir = archived.get(&topic_id).cloned().unwrap_or_else(|| {
+        let site = client.site();
+        let mut claimed = claimed.lock().unwrap();
+        // Topics may share the same title, and are archived concurrently.
+        let dir = root.join(topic_dir_name(site, &topic_meta.title));
+        let dir = if dir.exists() || claimed.contains(&dir) {
+            root.join(topic_dir_name(
+                site,
+                &format!("{}_{}", topic_meta.title, topic_id),
+            ))
+        } else {
+            dir
+        };
+        claimed.insert(dir.clone());
+        dir
+    });
+
+    // Progress of a single topic is not shown among concurrent ones.
+    let (tx, mut rx) = mpsc::channel(8);
+    tokio::spawn(async move { while rx.recv().await.is_some() {} });
+    archiver::archive(client, topic_meta, &dir, options, tx).await?;
+    Ok(dir)
+}
+
+fn print_summary(outcomes: &[Outcome], root: &Path) {
+    let width = outcomes
+        .iter()
+        .map(|o| measure_text_width(o.title.as_deref().unwrap_or(&o.entry)))
+        .max()
+        .unwrap_or_default()
+        .min(40);
+    eprintln!();
+    for outcome in outcomes {
+        let name = outcome.title.as_deref().unwrap_or(&outcome.entry);
+        let name = pad_str(name, width, Alignment::Left, Some("…"));
+        match &outcome.result {
+            Ok(dir) => eprintln!(
+                "{}  {name}  {}",
+                style("OK    ").green(),
+                dir.strip_prefix(root).unwrap_or(dir).display()
+            ),
+            Err(e) => eprintln!("{}  {name}  {e}", style("FAILED").red()),
+        }
+    }
+    eprintln!();
+}
@@ -0,0 +1,346 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use console::style;
+use dialoguer::theme::ColorfulTheme;
+use dialoguer::MultiSelect;
+use indicatif::{MultiProgress, ProgressBar};
+
+use sa_core::archiver::{
+    bookmark_group, fetch_bookmarks, fetch_category, fetch_category_topics, fetch_current_user,
+    fetch_private_messages, fetch_search, fetch_tag_topics, fetch_topic_meta, find_archives,
+    scan_library, topic_dir_name, write_library_index, ArchiveOptions, Collection, CollectionEntry,
+    Manifest, Templates, TopicSummary, LIBRARY_INDEX,
+};
+use sa_core::client::Client;
+use sa_core::re_exports::reqwest::Url;
+use sa_core::site::Site;
+
+use crate::archive::{archive_topic, connect};
+
+pub async fn archive_category(
+    token: &str,
+    site: Site,
+    category_id: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let templates = Templates::load(options.template_dir())?;
+    let progress = MultiProgress::new();
+
+    let spinner = ProgressBar::new_spinner().with_message("Fetching topic list...");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let client = connect(token, site, &progress).await?;
+    let category = fetch_category(&client, category_id).await?;
+    let topics = fetch_category_topics(&client, &category).await?;
+
+    spinner.finish_with_message(format!("Fetching topic list... {} topics", topics.len()));
+
+    let entries = archive_topics(&client, &progress, &topics, save_to, options, false).await?;
+    let collection = Collection {
+        kind: String::from("分类"),
+        source: client.site().url(&category.path()),
+        name: category.name,
+        description: category.description,
+        entries,
+    };
+    finish(client.site(), &collection, save_to, &templates)
+}
+
+pub async fn archive_tag(
+    token: &str,
+    site: Site,
+    tag: &str,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let templates = Templates::load(options.template_dir())?;
+    let progress = MultiProgress::new();
+
+    let spinner = ProgressBar::new_spinner().with_message("Fetching topic list...");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let client = connect(token, site, &progress).await?;
+    let topics = fetch_tag_topics(&client, tag).await?;
+
+    spinner.finish_with_message(format!("Fetching topic list... {} topics", topics.len()));
+
+    let entries = archive_topics(&client, &progress, &topics, save_to, options, true).await?;
+    let collection = Collection {
+        kind: String::from("标签"),
+        name: tag.to_string(),
+        description: None,
+        source: client.site().url(&format!("/tag/{tag}")),
+        entries,
+    };
+    finish(client.site(), &collection, save_to, &templates)
+}
+
+pub async fn archive_search(
+    token: &str,
+    site: Site,
+    query: &str,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let templates = Templates::load(options.template_dir())?;
+    let progress = MultiProgress::new();
+
+    let spinner = ProgressBar::new_spinner().with_message("Searching...");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let client = connect(token, site, &progress).await?;
+    let result = fetch_search(&client, query).await?;
+
+    spinner.finish_with_message(format!("Searching... {} topics", result.topics.len()));
+
+    let entries =
+        archive_topics(&client, &progress, &result.topics, save_to, options, false).await?;
+    let collection = Collection {
+        kind: String::from("搜索"),
+        name: query.to_string(),
+        description: None,
+        source: Url::parse_with_params(&client.site().url("/search"), &[("q", query)])?.into(),
+        entries,
+    };
+    finish(client.site(), &collection, save_to, &templates)
+}
+
+/// Archive private messages of a user.
+///
+/// Only messages with given ids are archived if any. If `topic_ids` is empty, the user is asked to
+/// pick them. If it's `None`, all messages are archived.
+pub async fn archive_private_messages(
+    token: &str,
+    site: Site,
+    username: &str,
+    topic_ids: Option<Vec<u32>>,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let templates = Templates::load(options.template_dir())?;
+    let progress = MultiProgress::new();
+
+    let spinner = ProgressBar::new_spinner().with_message("Fetching messages...");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let client = connect(token, site, &progress).await?;
+    let messages = fetch_private_messages(&client, username).await?;
+
+    spinner.finish_with_message(format!("Fetching messages... {} messages", messages.len()));
+
+    let topics: Vec<TopicSummary> = match topic_ids {
+        None => messages,
+        Some(ids) if !ids.is_empty() => messages
+            .into_iter()
+            .filter(|topic| ids.contains(&topic.id))
+            .collect(),
+        Some(_) => {
+            let titles: Vec<&str> = messages.iter().map(|topic| topic.title.as_str()).collect();
+            let picked = MultiSelect::with_theme(&ColorfulTheme::default())
+                .with_prompt("Pick messages to archive")
+                .items(&titles)
+                .interact()?;
+            messages
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| picked.contains(idx))
+                .map(|(_, topic)| topic)
+                .collect()
+        }
+    };
+
+    let entries = archive_topics(&client, &progress, &topics, save_to, options, false).await?;
+    let collection = Collection {
+        kind: String::from("私信"),
+        name: username.to_string(),
+        description: None,
+        source: client.site().url(&format!("/u/{username}/messages")),
+        entries,
+    };
+    finish(client.site(), &collection, save_to, &templates)
+}
+
+pub async fn archive_bookmarks(
+    token: &str,
+    site: Site,
+    save_to: &Path,
+    options: &ArchiveOptions,
+) -> anyhow::Result<()> {
+    let templates = Templates::load(options.template_dir())?;
+    let progress = MultiProgress::new();
+
+    let spinner = ProgressBar::new_spinner().with_message("Fetching bookmarks...");
+    spinner.enable_steady_tick(Duration::from_millis(100));
+
+    let client = connect(token, site, &progress).await?;
+    let user = fetch_current_user(&client).await?;
+    let bookmarks = fetch_bookmarks(&client, &user.username).await?;
+
+    spinner.finish_with_message(format!(
+        "Fetching bookmarks... {} bookmarks",
+        bookmarks.len()
+    ));
+
+    // A topic is listed under the group of its newest bookmark.
+    let mut groups = HashMap::new();
+    let mut topics = vec![];
+    for bookmark in &bookmarks {
+        if groups.contains_key(&bookmark.topic_id) {
+            continue;
+        }
+        groups.insert(bookmark.topic_id, bookmark_group(bookmark));
+        topics.push(TopicSummary {
+            id: bookmark.topic_id,
+            title: bookmark.title.clone(),
+            category_id: None,
+            posts_count: bookmark.highest_post_number,
+            last_posted_at: bookmark.bumped_at,
+        });
+    }
+
+    let mut entries = archive_topics(&client, &progress, &topics, save_to, options, false).await?;
+    for entry in &mut entries {
+        entry.group = groups.remove(&entry.id).flatten();
+    }
+    let collection = Collection {
+        kind: String::from("书签"),
+        source: client
+            .site()
+            .url(&format!("/u/{}/activity/bookmarks", user.username)),
+        name: user.username,
+        description: None,
+        entries,
+    };
+    finish(client.site(), &collection, save_to, &templates)
+}
+
+/// Archive given topics into subdirectories of the root.
+///
+/// Existing archives under the root are updated, or skipped if `skip_archived` is set. A failed
+/// topic doesn't stop the others.
+async fn archive_topics(
+    client: &Client,
+    progress: &MultiProgress,
+    topics: &[TopicSummary],
+    root: &Path,
+    options: &ArchiveOptions,
+    skip_archived: bool,
+) -> anyhow::Result<Vec<CollectionEntry>> {
+    fs::create_dir_all(root)?;
+    let archived = find_archives(root);
+
+    let mut entries = Vec::with_capacity(topics.len());
+    for (idx, topic) in topics.iter().enumerate() {
+        eprintln!(
+            "{}",
+            style(format!("[{}/{}] {}", idx + 1, topics.len(), topic.title)).bold()
+        );
+        let entry = archive_entry(
+            client,
+            progress,
+            topic,
+            root,
+            &archived,
+            options,
+            skip_archived,
+        )
+        .await
+        .unwrap_or_else(|e| {
+            eprintln!("{}", style(format!("Failed to archive: {e}")).red());
+            CollectionEntry::failed(client.site(), topic)
+        });
+        entries.push(entry);
+    }
+    Ok(entries)
+}
+
+async fn archive_entry(
+    client: &Client,
+    progress: &MultiProgress,
+    topic: &TopicSummary,
+    root: &Path,
+    archived: &HashMap<u32, PathBuf>,
+    options: &ArchiveOptions,
+    skip_archived: bool,
+) -> anyhow::Result<CollectionEntry> {
+    let site = client.site();
+    if let Some(dir) = archived.get(&topic.id).filter(|_| skip_archived) {
+        eprintln!("{}", style("Already archived, skipped.").dim());
+        let manifest = Manifest::load(dir)?;
+        return Ok(CollectionEntry::archived(
+            site,
+            topic,
+            &manifest.topic,
+            dir.strip_prefix(root)?,
+            manifest.format,
+        ));
+    }
+
+    let topic_meta = fetch_topic_meta(client, topic.id).await?;
+    let dir = archived.get(&topic.id).cloned().unwrap_or_else(|| {
+        // Topics may share the same title.
+        let dir = root.join(topic_dir_name(site, &topic_meta.title));
+        if dir.exists() {
+            root.join(topic_dir_name(
+                site,
+                &format!("{}_{}", topic_meta.title, topic.id),
+            ))
+        } else {
+            dir
+        }
+    });
+    archive_topic(client, progress, topic_meta.clone(), &dir, options).await?;
+    Ok(CollectionEntry::archived(
+        site,
+        topic,
+        &topic_meta,
+        dir.strip_prefix(root)?,
+        options.format,
+    ))
+}
+
+pub fn index_library(root: &Path, template_dir: Option<&Path>) -> anyhow::Result<()> {
+    let templates = Templates::load(template_dir)?;
+    let entries = scan_library(root);
+    write_library_index(&entries, root, &templates)?;
+
+    eprintln!(
+        "{}",
+        style(format!("Done. {} archives found.", entries.len())).green()
+    );
+    println!("{}", root.join(LIBRARY_INDEX).display());
+    Ok(())
+}
+
+fn finish(
+    site: &Site,
+    collection: &Collection,
+    root: &Path,
+    templates: &Templates,
+) -> anyhow::Result<()> {
+    collection.write_index(site, root, templates)?;
+
+    let failed = collection
+        .entries
+        .iter()
+        .filter(|entry| entry.link.is_none())
+        .count();
+    if failed == 0 {
+        eprintln!("{}", style("Done.").green());
+    } else {
+        eprintln!(
+            "{}",
+            style(format!(
+                "Done. {failed} of {} topics failed to archive.",
+                collection.entries.len()
+            ))
+            .yellow()
+        );
+    }
+    println!("{}", root.display());
+    Ok(())
+}
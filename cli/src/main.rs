@@ -1,7 +1,7 @@
 use std::process::ExitCode;
 use std::str::FromStr;
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, bail, Result};
 use clap::Parser;
 use console::style;
 use once_cell::sync::Lazy;
@@ -9,13 +9,18 @@ use regex::Regex;
 use tracing_subscriber::EnvFilter;
 
 use sa_core::re_exports::uuid::Uuid;
+use sa_core::site::{Site, SHUIYUAN_BASE_URL};
 
-use crate::args::{Archive, Commands, Opts};
+use crate::args::{
+    Archive, BatchArchive, CategoryArchive, Commands, Opts, SearchArchive, TagArchive, UserArchive,
+};
 use crate::auth::auth;
 
 mod archive;
 mod args;
 mod auth;
+mod batch;
+mod collection;
 
 static APP_ID: Lazy<Uuid> =
     Lazy::new(|| Uuid::from_str("db559e8d-1bb1-4cf1-a5b8-b5cb4e05ea82").unwrap());
@@ -37,35 +42,200 @@ async fn entry() -> Result<()> {
 
     let opts = Opts::parse();
     match opts.command {
-        Commands::Auth { no_open } => auth(no_open),
+        Commands::Auth { no_open, site } => auth(no_open, &Site::new(&site)),
         Commands::Archive(Archive {
             topic_id,
             url,
             save_to,
-            anonymous,
-            token,
+            common,
             create_subdir,
             no_create_subdir,
         }) => {
-            static RE_URL: Lazy<Regex> =
-                Lazy::new(|| Regex::new(r#"https://shuiyuan.sjtu.edu.cn/t/topic/(\d+)"#).unwrap());
+            let site = Site::new(&common.site);
             let topic = if let Some(url) = url {
-                RE_URL
-                    .captures(&url)
-                    .and_then(|caps| caps.get(1).expect("regex match").as_str().parse().ok())
-                    .ok_or_else(|| anyhow!("Invalid token."))?
+                archive::topic_id_from_url(&url, &site)?
             } else {
                 topic_id.expect("clap arg match")
             };
-            let token = token
-                .or_else(|| std::env::var("SHUIYUAN_TOKEN").ok())
-                .ok_or_else(|| anyhow!("Missing token. Please specify an API token via `token` argument or `SHUIYUAN_TOKEN` environment variable."))?;
+            let token = resolve_token(common.token.clone(), &common.site)?;
 
             let create_subdir = create_subdir
                 .then_some(true)
                 .or_else(|| no_create_subdir.then_some(false));
 
-            archive::archive(&token, topic, &save_to, anonymous, create_subdir).await
+            archive::archive(
+                &token,
+                site,
+                topic,
+                &save_to,
+                &common.archive_options(),
+                create_subdir,
+            )
+            .await
+        }
+        Commands::Category(CategoryArchive {
+            category_id,
+            url,
+            save_to,
+            common,
+        }) => {
+            static RE_URL: Lazy<Regex> =
+                Lazy::new(|| Regex::new(r#"/c/(?:[^/]+/)*(\d+)"#).unwrap());
+            let category = if let Some(url) = url {
+                RE_URL
+                    .captures(&url)
+                    .and_then(|caps| caps.get(1).expect("regex match").as_str().parse().ok())
+                    .ok_or_else(|| anyhow!("Invalid URL."))?
+            } else {
+                category_id.expect("clap arg match")
+            };
+            let token = resolve_token(common.token.clone(), &common.site)?;
+
+            collection::archive_category(
+                &token,
+                Site::new(&common.site),
+                category,
+                &save_to,
+                &common.archive_options(),
+            )
+            .await
         }
+        Commands::User(UserArchive {
+            username,
+            save_to,
+            context,
+            common,
+        }) => {
+            let token = resolve_token(common.token.clone(), &common.site)?;
+            archive::archive_user(
+                &token,
+                Site::new(&common.site),
+                username.trim_start_matches('@'),
+                context,
+                &save_to,
+                &common.archive_options(),
+            )
+            .await
+        }
+        Commands::Tag(TagArchive {
+            tag,
+            save_to,
+            common,
+        }) => {
+            let token = resolve_token(common.token.clone(), &common.site)?;
+            collection::archive_tag(
+                &token,
+                Site::new(&common.site),
+                &tag,
+                &save_to,
+                &common.archive_options(),
+            )
+            .await
+        }
+        Commands::Search(SearchArchive {
+            query,
+            save_to,
+            posts_only,
+            context,
+            common,
+        }) => {
+            let token = resolve_token(common.token.clone(), &common.site)?;
+            if posts_only {
+                archive::archive_search_posts(
+                    &token,
+                    Site::new(&common.site),
+                    &query,
+                    context,
+                    &save_to,
+                    &common.archive_options(),
+                )
+                .await
+            } else {
+                collection::archive_search(
+                    &token,
+                    Site::new(&common.site),
+                    &query,
+                    &save_to,
+                    &common.archive_options(),
+                )
+                .await
+            }
+        }
+        Commands::Batch(BatchArchive {
+            list,
+            save_to,
+            jobs,
+            common,
+        }) => {
+            let token = resolve_token(common.token.clone(), &common.site)?;
+            batch::archive_batch(
+                &token,
+                Site::new(&common.site),
+                &list,
+                &save_to,
+                &common.archive_options(),
+                jobs,
+            )
+            .await
+        }
+        Commands::Pm(PmArchive {
+            username,
+            save_to,
+            all,
+            topic_ids,
+            common,
+        }) => {
+            let token = resolve_token(common.token.clone(), &common.site)?;
+            collection::archive_private_messages(
+                &token,
+                Site::new(&common.site),
+                username.trim_start_matches('@'),
+                (!all).then_some(topic_ids),
+                &save_to,
+                &common.archive_options(),
+            )
+            .await
+        }
+        Commands::Bookmarks(BookmarksArchive {
+            save_to,
+            posts_only,
+            context,
+            common,
+        }) => {
+            let token = resolve_token(common.token.clone(), &common.site)?;
+            if posts_only {
+                archive::archive_bookmark_posts(
+                    &token,
+                    Site::new(&common.site),
+                    context,
+                    &save_to,
+                    &common.archive_options(),
+                )
+                .await
+            } else {
+                collection::archive_bookmarks(
+                    &token,
+                    Site::new(&common.site),
+                    &save_to,
+                    &common.archive_options(),
+                )
+                .await
+            }
+        }
+        Commands::Library { root, template_dir } => {
+            collection::index_library(&root, template_dir.as_deref())
+        }
+    }
+}
+
+/// Token given by `--token`, falling back to `SHUIYUAN_TOKEN`.
+///
+/// The environment variable holds a Shuiyuan token, so it's never sent to other sites.
+fn resolve_token(token: Option<String>, site: &str) -> Result<String> {
+    if token.is_none() && Site::new(site).base_url != SHUIYUAN_BASE_URL {
+        bail!("Missing token. Please specify an API token for {site} via `token` argument. `SHUIYUAN_TOKEN` environment variable only applies to Shuiyuan BBS.");
     }
+    token
+        .or_else(|| std::env::var("SHUIYUAN_TOKEN").ok())
+        .ok_or_else(|| anyhow!("Missing token. Please specify an API token via `token` argument or `SHUIYUAN_TOKEN` environment variable."))
 }
@@ -5,7 +5,7 @@ pub const ACTION_CODE_MAP: &[(&str, &str)] = &[
     ("split_topic", "拆分了此话题"),
     ("invited_user", "邀请了用户"),
     ("invited_group", "邀请了组"),
-    ("user_left", "在将自己从此消息中移除"),
+    ("user_left", "将自己从此消息中移除"),
     ("removed_user", "移除了用户"),
     ("removed_group", "移除了组"),
     ("autobumped", "自动提升"),
@@ -1,37 +1,78 @@
 //! Well this file is really a mess. Good luck if you try to modify it.
 
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::fs::File;
 use std::path::Path;
 use std::sync::Arc;
 
 use futures::stream::FuturesOrdered;
 use futures::TryStreamExt;
+use handlebars::Handlebars;
 use once_cell::sync::Lazy;
 use regex::Regex;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::Barrier;
 use typeshare::typeshare;
 
-pub use fetchers::fetch_topic_meta;
+pub use fetchers::{
+    fetch_bookmarks, fetch_category, fetch_category_topics, fetch_current_user,
+    fetch_private_messages, fetch_search, fetch_tag_topics, fetch_topic_meta,
+};
 
+pub use crate::archiver::bookmarks::{archive_bookmarks, bookmark_group, BOOKMARKS_PAGE};
+pub use crate::archiver::checkpoint::checkpoint_exists;
+use crate::archiver::checkpoint::Checkpoint;
+pub use crate::archiver::collection::{
+    find_archives, topic_dir_name, Collection, CollectionEntry, COLLECTION_INDEX,
+};
 use crate::archiver::download_manager::DownloadManager;
-use crate::archiver::template::HANDLEBARS;
+pub use crate::archiver::library::{
+    scan_library, write_library_index, LibraryEntry, LIBRARY_INDEX,
+};
+pub use crate::archiver::manifest::{AssetManifest, Manifest, PostManifest, MANIFEST_FILE};
+pub use crate::archiver::options::{
+    ArchiveFormat, ArchiveOptions, AttachmentOptions, Pagination, Theme, DEFAULT_PAGE_SIZE,
+};
+use crate::archiver::record::{ArchiveRecord, ArchivedPost};
+pub use crate::archiver::search::{archive_search, search_page_filename};
+pub use crate::archiver::template::Templates;
+pub use crate::archiver::user::{archive_user, user_page_filename};
 use crate::client::{Client, RequestBuilderExt, MAX_CONN, MAX_THROTTLE_WEIGHT};
 use crate::error::{Error, Result};
-pub use crate::models::{Category, TopicMeta};
-use crate::models::{Params, Post, RespPost, RespPosts, Topic};
-use crate::preloaded_store::PreloadedStore;
+pub use crate::models::{
+    Bookmark, Category, CategoryInfo, SearchPost, SearchResult, TopicMeta, TopicSummary,
+};
+use crate::models::{Params, Post, RespPost, RespPosts, RespRetort, Topic};
+use crate::preloaded_store::{PreloadedStore, Stylesheet};
+use crate::site::Site;
 
 mod anonymous;
+mod bookmarks;
+mod checkpoint;
+mod collection;
 mod download_manager;
+mod epub;
 mod fetchers;
+mod json;
+mod library;
+mod manifest;
+mod markdown;
+mod options;
+mod pagination;
+mod post_list;
+mod record;
+mod search;
+mod search_index;
+mod single_html;
+mod stylesheets;
 mod template;
+mod thread;
+mod user;
 mod utils;
 
 const FETCH_PAGE_SIZE: usize = 400;
-const EXPORT_PAGE_SIZE: usize = 20;
 
 /// Download events.
 #[typeshare]
@@ -51,11 +92,19 @@ pub enum DownloadEvent {
 
 /// Archive given topic into directory.
 ///
+/// If the directory holds an archive of the same topic made by this version of archiver, it's
+/// updated incrementally: only new and changed posts are processed, and only affected pages are
+/// rendered. All posts are still fetched in chunks to learn which are edited, because editing an
+/// old post doesn't bump the topic; likes and reactions of posts reused from last run are updated
+/// from them too. Anonymous archives are always rebuilt because the record of a previous run
+/// would leak real usernames, and so are single HTML archives because their resources are
+/// removed once inlined.
+///
 /// # Arguments
 ///
 /// * `topic_id` - The topic id to archive.
 /// * `save_to_base` - The base directory to save the archive to.
-/// * `anonymous` - Whether to anonymize usernames.
+/// * `options` - Archive options, e.g. whether to anonymize usernames and the output format.
 /// * `reporter` - The sender to send download events to.
 ///
 /// # Errors
@@ -63,32 +112,75 @@ pub enum DownloadEvent {
 /// There are many possible errors. See the `Error` enum for details.
 pub async fn archive(
     client: &Client,
-    topic_meta: TopicMeta,
+    mut topic_meta: TopicMeta,
     save_to: &Path,
-    anonymous: bool,
+    options: &ArchiveOptions,
     reporter: Sender<DownloadEvent>,
 ) -> Result<()> {
+    let anonymous = options.anonymous;
+    if anonymous && options.format == ArchiveFormat::Json {
+        // Raw responses are full of usernames and can't be masked reliably.
+        return Err(Error::UnsupportedOptions(
+            "raw JSON output can't be anonymized",
+        ));
+    }
+
+    // Compile templates first, so that broken ones are reported before anything is downloaded.
+    let handlebars = template::handlebars(options.template_dir())?;
+
     // Fetch preload emojis.
     let preloaded_store = PreloadedStore::from_client(client).await?;
 
     // 1. Create directories and extract resources.
     fs::create_dir_all(save_to.join("resources"))?;
-    template::extract_resources(save_to.join("resources"))?;
+    if matches!(
+        options.format,
+        ArchiveFormat::Html | ArchiveFormat::SingleHtml
+    ) {
+        template::extract_resources(save_to.join("resources"), options.template_dir())?;
+    }
 
-    // 2. Fetch all posts and download assets.
+    // 2. Load the record of last run.
+    let rebuild = anonymous || options.format == ArchiveFormat::SingleHtml;
+    let record = if rebuild {
+        ArchiveRecord::remove(save_to)?;
+        ArchiveRecord::default()
+    } else {
+        ArchiveRecord::load(save_to, client.site(), &topic_meta, options)
+    };
+
+    // 3. Fetch all posts and download assets of new or changed posts. Fetched chunks are
+    //    checkpointed so that a failed run can be resumed.
+    let checkpoint = Checkpoint::open(save_to, client.site(), &topic_meta, anonymous)?;
     let download_manager =
         DownloadManager::new(client.clone(), save_to.to_path_buf(), reporter.clone());
-    let mut posts = archive_resp_posts(
+    let (archived, raw_chunks) = archive_resp_posts(
         client,
         &download_manager,
         &preloaded_store,
-        anonymous,
+        &record,
+        &checkpoint,
+        options,
         &topic_meta,
         reporter,
     )
     .await?;
+    // Downloaded after posts, so that progress of posts is reported first.
+    let stylesheets = if options.live_styles
+        && matches!(
+            options.format,
+            ArchiveFormat::Html | ArchiveFormat::SingleHtml
+        ) {
+        stylesheets::download_stylesheets(&download_manager, &preloaded_store).await
+    } else {
+        vec![]
+    };
+    let layout = serde_json::to_string(&(options.theme, &options.template_dir, &stylesheets))?;
+    let relayout = record.is_layout_changed(&layout) || record.is_meta_changed(&topic_meta);
+    let changed = record.changed_posts(&archived);
+    let mut posts: Vec<Post> = archived.iter().map(|p| p.post.clone()).collect();
 
-    // 3. If anonymous mode enabled, mask all usernames.
+    // 4. If anonymous mode enabled, mask all usernames.
     if anonymous {
         let fake_name_map = anonymous::collect_anonymous_names(&posts);
         for post in &mut posts {
@@ -100,29 +192,217 @@ pub async fn archive(
             post.avatar = None;
             post.content = anonymous::mask_username_in_cooked(&fake_name_map, post.content.clone());
         }
+        topic_meta.participants = anonymous::mask_names(&fake_name_map, &topic_meta.participants);
     }
 
-    // 4. Write posts to files.
-    posts
-        .chunks(EXPORT_PAGE_SIZE)
-        .enumerate()
-        .try_for_each(move |(page, group)| {
-            write_page(topic_meta.clone(), page + 1, group, save_to)
-        })?;
+    // 5. If threaded mode enabled, order posts along their reply trees.
+    let templated = matches!(
+        options.format,
+        ArchiveFormat::Html | ArchiveFormat::SingleHtml
+    );
+    let (archived, mut posts) = if options.threaded && templated {
+        let order = thread::thread_order(&posts);
+        let mut slots: Vec<Option<(ArchivedPost, Post)>> =
+            archived.into_iter().zip(posts).map(Some).collect();
+        order
+            .into_iter()
+            .map(|(idx, position)| {
+                let (archived, mut post) = slots[idx].take().expect("visited once");
+                post.thread = Some(position);
+                (archived, post)
+            })
+            .unzip()
+    } else {
+        // Posts reused from last run may carry their old positions.
+        for post in &mut posts {
+            post.thread = None;
+        }
+        (archived, posts)
+    };
+
+    // 6. Write affected pages to files.
+    let pagination = if matches!(options.format, ArchiveFormat::Html | ArchiveFormat::Epub) {
+        options.pagination
+    } else {
+        Pagination::Single
+    };
+    let ranges = pagination::paginate(&posts, pagination, &save_to.join("resources"));
+    if templated {
+        thread::link_replies(&mut posts, &ranges);
+    }
+    // Positions in reply trees and links to parents change with other posts, so posts rendered
+    // differently from last run are changed too.
+    let changed: HashSet<u32> = archived
+        .iter()
+        .zip(&posts)
+        .filter(|(archived, post)| {
+            archived.post.thread != post.thread || archived.post.reply_to_link != post.reply_to_link
+        })
+        .map(|(archived, _)| archived.id)
+        .chain(changed)
+        .collect();
+    let pages: Vec<Vec<u32>> = ranges
+        .iter()
+        .map(|range| archived[range.clone()].iter().map(|p| p.id).collect())
+        .collect();
+    let total_pages = pages.len();
+    let manifest = Manifest::build(
+        save_to,
+        client.site(),
+        &topic_meta,
+        options,
+        &archived,
+        &posts,
+        &pages,
+        download_manager.saved_files(),
+    );
+    match options.format {
+        ArchiveFormat::Html => {
+            ranges
+                .iter()
+                .zip(&pages)
+                .enumerate()
+                .filter(|(page, (_, ids))| {
+                    relayout
+                        || record.is_page_dirty(*page, ids, total_pages, &changed)
+                        || !save_to.join(page_filename(page + 1)).exists()
+                })
+                .try_for_each(|(page, (range, _))| {
+                    write_page(
+                        &handlebars,
+                        client.site(),
+                        topic_meta.clone(),
+                        page + 1,
+                        total_pages,
+                        &posts[range.clone()],
+                        &stylesheets,
+                        options,
+                        save_to,
+                    )
+                })?;
+            search_index::write_search_index(&posts, &ranges, save_to)?;
+            // Remove pages left over from a run with more pages.
+            for page in total_pages + 1.. {
+                let path = save_to.join(page_filename(page));
+                if !path.exists() {
+                    break;
+                }
+                fs::remove_file(path)?;
+            }
+        }
+        ArchiveFormat::Markdown => {
+            markdown::write_markdown(client.site(), &topic_meta, &posts, save_to)?;
+        }
+        ArchiveFormat::Json => {
+            let raw_topic = match &topic_meta.raw {
+                Some(raw) => raw.clone(),
+                None => Arc::new(fetchers::fetch_topic_raw(client, topic_meta.id).await?),
+            };
+            json::write_json(&raw_topic, &raw_chunks, &archived, save_to)?;
+        }
+        ArchiveFormat::SingleHtml => {
+            let rendered = render_page(
+                &handlebars,
+                client.site(),
+                topic_meta.clone(),
+                1,
+                1,
+                &posts,
+                &stylesheets,
+                options,
+            )?;
+            let size_limit = options
+                .inline_limit_mib
+                .map(|mib| u64::from(mib) * 1024 * 1024);
+            single_html::write_single_html(&rendered, &manifest.assets, size_limit, save_to)?;
+            // Everything needed is in the page now.
+            fs::remove_dir_all(save_to.join("resources"))?;
+        }
+        ArchiveFormat::Epub => {
+            let pages: Vec<&[Post]> = ranges.iter().map(|range| &posts[range.clone()]).collect();
+            epub::write_epub(
+                client.site(),
+                &topic_meta,
+                &pages,
+                &manifest.assets,
+                save_to,
+            )?;
+        }
+    }
+
+    // 7. Write the manifest.
+    manifest.save(save_to)?;
+
+    // 8. Save the record for next run, along with how posts were rendered.
+    if !rebuild {
+        let archived = archived
+            .into_iter()
+            .zip(posts)
+            .map(|(archived, post)| ArchivedPost { post, ..archived })
+            .collect();
+        ArchiveRecord::new(client.site(), &topic_meta, options, layout, pages, archived)
+            .save(save_to)?;
+    }
+    checkpoint.finish()?;
 
     Ok(())
 }
 
-fn write_page(meta: TopicMeta, page: usize, posts: &[Post], save_to: &Path) -> Result<()> {
-    let post_count = meta.post_ids.len();
-    let total_pages = utils::ceil_div(post_count, EXPORT_PAGE_SIZE);
+fn page_filename(page: usize) -> String {
+    if page == 1 {
+        String::from("index.html")
+    } else {
+        format!("{page}.html")
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn write_page(
+    handlebars: &Handlebars,
+    site: &Site,
+    meta: TopicMeta,
+    page: usize,
+    total_pages: usize,
+    posts: &[Post],
+    stylesheets: &[Stylesheet],
+    options: &ArchiveOptions,
+    save_to: &Path,
+) -> Result<()> {
+    let output = render_page(
+        handlebars,
+        site,
+        meta,
+        page,
+        total_pages,
+        posts,
+        stylesheets,
+        options,
+    )?;
+    fs::write(save_to.join(page_filename(page)), output)?;
+    Ok(())
+}
+
+#[allow(clippy::too_many_arguments)]
+fn render_page(
+    handlebars: &Handlebars,
+    site: &Site,
+    meta: TopicMeta,
+    page: usize,
+    total_pages: usize,
+    posts: &[Post],
+    stylesheets: &[Stylesheet],
+    options: &ArchiveOptions,
+) -> Result<String> {
     let last_page = page == total_pages;
+    let threaded = posts.iter().any(|post| post.thread.is_some());
     let topic = Topic {
         id: meta.id,
         title: meta.title,
         description: meta.description,
         categories: meta.categories,
         tags: meta.tags,
+        private: meta.private,
+        participants: meta.participants,
         posts,
         page,
         total_pages,
@@ -132,25 +412,27 @@ fn write_page(meta: TopicMeta, page: usize, posts: &[Post], save_to: &Path) -> R
             _ => Some(format!("{}", page - 1)),
         },
         next_page: if last_page { None } else { Some(page + 1) },
+        // Only paginated output comes with a search index.
+        search_index: options.format == ArchiveFormat::Html,
+        threaded,
+        theme: options.theme,
+        stylesheets,
     };
-    let params = Params::from(topic);
-    let filename = if page == 1 {
-        String::from("index.html")
-    } else {
-        format!("{page}.html")
-    };
-    let output = File::create(save_to.join(filename))?;
-    Ok(HANDLEBARS.render_to_write("index", &params, output)?)
+    let params = Params::new(topic, site);
+    Ok(handlebars.render("index", &params)?)
 }
 
+#[allow(clippy::too_many_arguments)]
 async fn archive_resp_posts(
     client: &Client,
     download_manager: &DownloadManager,
     preloaded_store: &PreloadedStore,
-    anonymous: bool,
+    record: &ArchiveRecord,
+    checkpoint: &Checkpoint,
+    options: &ArchiveOptions,
     topic_meta: &TopicMeta,
     reporter: Sender<DownloadEvent>,
-) -> Result<Vec<Post>> {
+) -> Result<(Vec<ArchivedPost>, Vec<Value>)> {
     let topic_id = topic_meta.id;
     let posts_total = topic_meta.post_ids.len();
     let chunks_total = utils::ceil_div(posts_total, FETCH_PAGE_SIZE);
@@ -162,11 +444,12 @@ async fn archive_resp_posts(
     let futs: FuturesOrdered<_> = topic_meta
         .post_ids
         .chunks(FETCH_PAGE_SIZE)
-        .map(move |post_ids| {
+        .enumerate()
+        .map(move |(idx, post_ids)| {
             let reporter = reporter.clone();
             let barrier = barrier.clone();
 
-            let url = format!("https://shuiyuan.sjtu.edu.cn/t/{topic_id}/posts.json");
+            let url = client.site().url(&format!("/t/{topic_id}/posts.json"));
             let query: Vec<_> = post_ids.iter().map(|i| ("post_ids[]", i)).collect();
             let req = client
                 .get(url)
@@ -174,7 +457,16 @@ async fn archive_resp_posts(
                 .with_conn_weight(MAX_CONN as u32)
                 .with_throttle_weight(MAX_THROTTLE_WEIGHT);
             async move {
-                let resp: RespPosts = client.send_json(req).await?;
+                let raw: Value = if let Some(raw) = checkpoint.chunk(idx, post_ids) {
+                    raw
+                } else {
+                    let raw = client.send_json(req).await?;
+                    checkpoint.save_chunk(idx, post_ids, &raw)?;
+                    raw
+                };
+                let resp = RespPosts::deserialize(&raw)?;
+                // Keep the response as is only if it's written into the archive.
+                let raw = (options.format == ArchiveFormat::Json).then_some(raw);
 
                 reporter
                     .send(DownloadEvent::PostChunksDownloadedInc)
@@ -186,39 +478,81 @@ async fn archive_resp_posts(
                     .post_stream
                     .posts
                     .into_iter()
-                    .map(|resp_post| {
-                        process_resp_post(
-                            client,
-                            download_manager,
-                            preloaded_store,
-                            anonymous,
-                            resp_post,
-                        )
+                    .map(|resp_post| async move {
+                        let id = resp_post.id as u32;
+                        let updated_at = resp_post.updated_at;
+                        let post = if let Some(post) = record.unchanged_post(id, &updated_at) {
+                            // Likes and reactions don't bump `updated_at`.
+                            Post {
+                                likes: likes_of_resp_post(&resp_post),
+                                emojis: fetch_emojis(
+                                    download_manager,
+                                    preloaded_store,
+                                    resp_post.retorts,
+                                )
+                                .await?,
+                                ..post.clone()
+                            }
+                        } else {
+                            process_resp_post(
+                                client,
+                                download_manager,
+                                preloaded_store,
+                                options,
+                                resp_post,
+                            )
+                            .await?
+                        };
+                        Ok::<_, Error>(ArchivedPost {
+                            id,
+                            updated_at,
+                            post,
+                        })
                     })
                     .collect();
-                let posts: Vec<Post> = futs.try_collect().await?;
-                Ok::<_, Error>(posts)
+                let posts: Vec<ArchivedPost> = futs.try_collect().await?;
+                Ok::<_, Error>((posts, raw))
             }
         })
         .collect();
 
-    let nested: Vec<Vec<Post>> = futs.try_collect().await?;
-    Ok(nested.into_iter().flatten().collect())
+    let nested: Vec<(Vec<ArchivedPost>, Option<Value>)> = futs.try_collect().await?;
+    let (posts, raw_chunks): (Vec<_>, Vec<_>) = nested.into_iter().unzip();
+    Ok((
+        posts.into_iter().flatten().collect(),
+        raw_chunks.into_iter().flatten().collect(),
+    ))
 }
 
 async fn process_resp_post(
     client: &Client,
     download_manager: &DownloadManager,
     preloaded_store: &PreloadedStore,
-    anonymous: bool,
+    options: &ArchiveOptions,
     resp_post: RespPost,
 ) -> Result<Post> {
     static RE_AVATAR: Lazy<Regex> = Lazy::new(|| Regex::new(r#"<img .* class="avatar">"#).unwrap());
 
+    let anonymous = options.anonymous;
+    // System messages, hidden posts and polls are converted from cooked content instead, because
+    // their raw sources lack what's shown on the site. Raw sources of anonymous archives can't be
+    // masked reliably either.
+    let raw = if options.format == ArchiveFormat::Markdown
+        && !anonymous
+        && resp_post.action_code.is_none()
+        && !resp_post.cooked_hidden
+        && resp_post.polls.is_empty()
+    {
+        fetchers::fetch_raw(client, resp_post.id).await?
+    } else {
+        None
+    };
+
     let resp_post = fetchers::fetch_special_post(client, resp_post).await?;
     let resp_post = fetchers::reify_vote(resp_post)?;
-    let cooked =
-        fetchers::fetch_assets_of_content(download_manager, &resp_post.cooked, anonymous).await?;
+    let (cooked, uploads) =
+        fetchers::fetch_assets_of_content(download_manager, &resp_post.cooked, options).await?;
+    let raw = raw.map(|raw| markdown::rewrite_upload_links(&raw, &uploads, client.site()));
     let (cooked, avatar) = if anonymous {
         (RE_AVATAR.replace_all(&cooked, "").to_string(), None)
     } else {
@@ -228,12 +562,7 @@ async fn process_resp_post(
         )
     };
     let likes = likes_of_resp_post(&resp_post);
-    let futs: FuturesOrdered<_> = resp_post
-        .retorts
-        .into_iter()
-        .map(|r| fetchers::fetch_emoji_from_retort(download_manager, preloaded_store, r))
-        .collect();
-    let emojis = futs.try_collect().await?;
+    let emojis = fetch_emojis(download_manager, preloaded_store, resp_post.retorts).await?;
 
     Ok(Post {
         name: resp_post.name,
@@ -246,9 +575,24 @@ async fn process_resp_post(
         reply_to: resp_post.reply_to_post_number,
         emojis,
         avatar,
+        raw,
+        reply_to_link: None,
+        thread: None,
     })
 }
 
+async fn fetch_emojis(
+    download_manager: &DownloadManager,
+    preloaded_store: &PreloadedStore,
+    retorts: Vec<RespRetort>,
+) -> Result<HashMap<String, usize>> {
+    let futs: FuturesOrdered<_> = retorts
+        .into_iter()
+        .map(|r| fetchers::fetch_emoji_from_retort(download_manager, preloaded_store, r))
+        .collect();
+    futs.try_collect().await
+}
+
 fn likes_of_resp_post(resp_post: &RespPost) -> usize {
     resp_post
         .actions_summary
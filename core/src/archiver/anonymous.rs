@@ -48,6 +48,19 @@ pub fn collect_anonymous_names<'a>(
     fake_name_map
 }
 
+/// Mask names not necessarily seen in posts, e.g. participants of a private message.
+pub fn mask_names(fake_name_map: &HashMap<String, String>, names: &[String]) -> Vec<String> {
+    names
+        .iter()
+        .map(|name| {
+            fake_name_map
+                .get(name)
+                .cloned()
+                .unwrap_or_else(|| Name().fake())
+        })
+        .collect()
+}
+
 pub fn mask_username_in_cooked(fake_name_map: &HashMap<String, String>, mut s: String) -> String {
     #[allow(clippy::type_complexity)]
     let re_f: &[(_, fn(&str) -> String)] = &[
@@ -0,0 +1,105 @@
+//! Archive of bookmarked posts of the current user.
+
+use std::collections::HashSet;
+use std::path::Path;
+
+use futures::stream::FuturesOrdered;
+use futures::TryStreamExt;
+use tokio::sync::mpsc::Sender;
+
+use crate::archiver::options::ArchiveOptions;
+use crate::archiver::post_list::{archive_post_list, context_window, PostList, TopicPosts};
+use crate::archiver::{fetchers, utils, DownloadEvent};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::models::Bookmark;
+
+/// Name of the page of a bookmark archive.
+pub const BOOKMARKS_PAGE: &str = "bookmarks.html";
+
+/// Group of a bookmark on index pages, i.e. its name, or its reminder if unnamed.
+#[must_use]
+pub fn bookmark_group(bookmark: &Bookmark) -> Option<String> {
+    bookmark
+        .name
+        .as_ref()
+        .filter(|name| !name.trim().is_empty())
+        .cloned()
+        .or_else(|| {
+            bookmark
+                .reminder_at
+                .as_ref()
+                .map(|at| format!("提醒：{}", utils::datetime_to_display(at)))
+        })
+}
+
+/// Archive posts bookmarked by the current user into a single page.
+///
+/// Each bookmarked post is shown with up to `context` posts before and after it in its topic. A
+/// bookmarked topic is shown by its first post. Topics are grouped by bookmark names or reminders.
+///
+/// # Errors
+///
+/// Returns error if failed to fetch bookmarks or posts, or write the page.
+/// Bookmark archives can't be anonymized and are only available in HTML.
+pub async fn archive_bookmarks(
+    client: &Client,
+    context: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+    reporter: Sender<DownloadEvent>,
+) -> Result<()> {
+    let user = fetchers::fetch_current_user(client).await?;
+    let bookmarks = fetchers::fetch_bookmarks(client, &user.username).await?;
+
+    // Bookmarks of the same topic in the same group are shown together.
+    let mut grouped: Vec<(Option<String>, u32, Vec<&Bookmark>)> = vec![];
+    for bookmark in &bookmarks {
+        let group = bookmark_group(bookmark);
+        match grouped
+            .iter_mut()
+            .find(|(g, topic_id, _)| *g == group && *topic_id == bookmark.topic_id)
+        {
+            Some((_, _, entries)) => entries.push(bookmark),
+            None => grouped.push((group, bookmark.topic_id, vec![bookmark])),
+        }
+    }
+    // Stable, so topics keep their order within a group.
+    let order: Vec<Option<String>> = grouped.iter().fold(vec![], |mut acc, (group, _, _)| {
+        if !acc.contains(group) {
+            acc.push(group.clone());
+        }
+        acc
+    });
+    grouped.sort_by_key(|(group, _, _)| order.iter().position(|g| g == group));
+
+    let futs: FuturesOrdered<_> = grouped
+        .into_iter()
+        .map(|(group, topic_id, entries)| async move {
+            let stream = fetchers::fetch_post_stream(client, topic_id).await?;
+            let highlighted: HashSet<u32> = entries
+                .iter()
+                .filter_map(|bookmark| bookmark.post_id().or_else(|| stream.first().copied()))
+                .collect();
+            Ok::<_, Error>(TopicPosts {
+                id: topic_id,
+                title: entries[0].title.clone(),
+                note: group,
+                post_ids: context_window(&stream, &highlighted, context),
+                highlighted,
+            })
+        })
+        .collect();
+    let topics: Vec<TopicPosts> = futs.try_collect().await?;
+
+    let list = PostList {
+        heading: format!("{} 的书签", user.username),
+        summary: format!("{} 个主题中共 {} 个书签", topics.len(), bookmarks.len()),
+        source: client
+            .site()
+            .url(&format!("/u/{}/activity/bookmarks", user.username)),
+        filename: String::from(BOOKMARKS_PAGE),
+        topics,
+    };
+    archive_post_list(client, list, save_to, options, reporter).await
+}
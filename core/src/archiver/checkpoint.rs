@@ -0,0 +1,111 @@
+//! On-disk checkpoint of an unfinished archive, so that it can be resumed after a failure.
+
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tempfile::NamedTempFile;
+use tracing::warn;
+
+use crate::error::Result;
+use crate::models::TopicMeta;
+use crate::site::Site;
+
+const CHECKPOINT_DIR: &str = ".archive-checkpoint";
+const CHECKPOINT_META: &str = "checkpoint.json";
+
+#[derive(Debug, PartialEq, Eq, Serialize, Deserialize)]
+struct CheckpointMeta {
+    app_version: String,
+    site: String,
+    topic_id: u32,
+    anonymous: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ChunkCheckpoint {
+    post_ids: Vec<u32>,
+    response: Value,
+}
+
+/// Fetched post chunks of an unfinished archive.
+///
+/// Assets are not tracked here because they are written atomically into `resources/` and
+/// skipped if they exist. The checkpoint lives in the target directory and is removed once the
+/// archive is finished.
+pub struct Checkpoint {
+    dir: PathBuf,
+}
+
+/// Check whether given directory holds an unfinished archive.
+#[must_use]
+pub fn checkpoint_exists(save_to: &Path) -> bool {
+    save_to.join(CHECKPOINT_DIR).join(CHECKPOINT_META).exists()
+}
+
+impl Checkpoint {
+    /// Open the checkpoint in given directory.
+    ///
+    /// Checkpoints of another topic, another mode or another version of archiver are discarded.
+    pub fn open(save_to: &Path, site: &Site, meta: &TopicMeta, anonymous: bool) -> Result<Self> {
+        let dir = save_to.join(CHECKPOINT_DIR);
+        let expected = CheckpointMeta {
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            site: site.base_url.clone(),
+            topic_id: meta.id,
+            anonymous,
+        };
+        let checkpoint = Self { dir };
+        if checkpoint.read::<CheckpointMeta>(CHECKPOINT_META).as_ref() != Some(&expected) {
+            if checkpoint.dir.exists() {
+                fs::remove_dir_all(&checkpoint.dir)?;
+            }
+            fs::create_dir_all(&checkpoint.dir)?;
+            checkpoint.write(CHECKPOINT_META, &expected)?;
+        }
+        Ok(checkpoint)
+    }
+
+    /// Get a previously fetched post chunk.
+    pub fn chunk(&self, idx: usize, post_ids: &[u32]) -> Option<Value> {
+        self.read::<ChunkCheckpoint>(&format!("chunk_{idx}.json"))
+            .filter(|chunk| chunk.post_ids == post_ids)
+            .map(|chunk| chunk.response)
+    }
+
+    pub fn save_chunk(&self, idx: usize, post_ids: &[u32], response: &Value) -> Result<()> {
+        self.write(
+            &format!("chunk_{idx}.json"),
+            &ChunkCheckpoint {
+                post_ids: post_ids.to_vec(),
+                response: response.clone(),
+            },
+        )
+    }
+
+    /// Remove the checkpoint after the archive is finished.
+    pub fn finish(self) -> Result<()> {
+        fs::remove_dir_all(&self.dir)?;
+        Ok(())
+    }
+
+    fn read<T: DeserializeOwned>(&self, name: &str) -> Option<T> {
+        let path = self.dir.join(name);
+        let data = fs::read(&path).ok()?;
+        serde_json::from_slice(&data)
+            .map_err(|e| warn!(?path, ?e, "Corrupted checkpoint file, ignoring"))
+            .ok()
+    }
+
+    fn write<T: Serialize>(&self, name: &str, value: &T) -> Result<()> {
+        // Write to a temporary file first so that an interrupted write never leaves a corrupted
+        // checkpoint behind.
+        let mut file = NamedTempFile::new_in(&self.dir)?;
+        file.write_all(&serde_json::to_vec(value)?)?;
+        file.persist(self.dir.join(name))?;
+        Ok(())
+    }
+}
@@ -0,0 +1,166 @@
+//! Archives of multiple topics under a common root, e.g. all topics in a category.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use sanitize_filename::sanitize;
+use serde::Serialize;
+
+use crate::archiver::manifest::Manifest;
+use crate::archiver::options::ArchiveFormat;
+use crate::archiver::template::Templates;
+use crate::archiver::utils;
+use crate::error::Result;
+use crate::models::{TopicMeta, TopicSummary};
+use crate::site::Site;
+
+pub const COLLECTION_INDEX: &str = "index.html";
+
+/// Topics archived together, and the index page linking them.
+#[derive(Debug, Clone, Serialize)]
+pub struct Collection {
+    /// Kind of the collection shown on the index page, e.g. "分类".
+    pub kind: String,
+    pub name: String,
+    pub description: Option<String>,
+    /// Where the topics are listed on the site.
+    pub source: String,
+    pub entries: Vec<CollectionEntry>,
+}
+
+/// A topic in a collection.
+#[derive(Debug, Clone, Serialize)]
+pub struct CollectionEntry {
+    pub id: u32,
+    pub title: String,
+    pub url: String,
+    /// Entry file of the archive relative to the collection root. `None` if archiving failed.
+    pub link: Option<String>,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    pub posts_count: usize,
+    pub last_posted_at: Option<String>,
+    /// Entries are grouped under this heading on the index page, if any.
+    pub group: Option<String>,
+}
+
+impl CollectionEntry {
+    /// Entry of an archived topic.
+    #[must_use]
+    pub fn archived(
+        site: &Site,
+        summary: &TopicSummary,
+        meta: &TopicMeta,
+        dir: &Path,
+        format: ArchiveFormat,
+    ) -> Self {
+        Self {
+            link: Some(format!(
+                "{}/{}",
+                url_escape(&dir.to_string_lossy()),
+                format.entry_file()
+            )),
+            categories: meta.categories.iter().map(|c| c.name.clone()).collect(),
+            tags: meta.tags.clone(),
+            ..Self::failed(site, summary)
+        }
+    }
+
+    /// Entry of a topic failed to archive, which links to the site instead.
+    #[must_use]
+    pub fn failed(site: &Site, summary: &TopicSummary) -> Self {
+        Self {
+            id: summary.id,
+            title: summary.title.clone(),
+            url: site.url(&format!("/t/topic/{}", summary.id)),
+            link: None,
+            categories: vec![],
+            tags: vec![],
+            posts_count: summary.posts_count,
+            last_posted_at: summary
+                .last_posted_at
+                .as_ref()
+                .map(utils::datetime_to_display),
+            group: None,
+        }
+    }
+}
+
+impl Collection {
+    /// Write the index page into the collection root.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if failed to render or write the page.
+    pub fn write_index(&self, site: &Site, root: &Path, templates: &Templates) -> Result<()> {
+        #[derive(Serialize)]
+        struct Group<'a> {
+            name: Option<&'a str>,
+            entries: Vec<&'a CollectionEntry>,
+        }
+        #[derive(Serialize)]
+        struct Params<'a> {
+            #[serde(flatten)]
+            collection: &'a Collection,
+            groups: Vec<Group<'a>>,
+            site: &'a Site,
+            total: usize,
+            app_version: &'static str,
+        }
+
+        // Groups are ordered by their first entries.
+        let mut groups: Vec<Group> = vec![];
+        for entry in &self.entries {
+            let name = entry.group.as_deref();
+            match groups.iter_mut().find(|group| group.name == name) {
+                Some(group) => group.entries.push(entry),
+                None => groups.push(Group {
+                    name,
+                    entries: vec![entry],
+                }),
+            }
+        }
+
+        let output = templates.render(
+            "collection",
+            &Params {
+                collection: self,
+                groups,
+                site,
+                total: self.entries.len(),
+                app_version: env!("CARGO_PKG_VERSION"),
+            },
+        )?;
+        fs::write(root.join(COLLECTION_INDEX), output)?;
+        Ok(())
+    }
+}
+
+/// Escape characters of a path which can't be put into a relative url.
+pub fn url_escape(path: &str) -> String {
+    path.replace('%', "%25")
+        .replace('#', "%23")
+        .replace('?', "%3F")
+}
+
+/// Directory name of a topic archive in a collection.
+#[must_use]
+pub fn topic_dir_name(site: &Site, title: &str) -> String {
+    sanitize(site.archive_dir_name(title))
+}
+
+/// Find archives directly under given root by their manifests, keyed by topic id.
+#[must_use]
+pub fn find_archives(root: &Path) -> HashMap<u32, PathBuf> {
+    let Ok(entries) = fs::read_dir(root) else {
+        return HashMap::new();
+    };
+    entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let manifest = Manifest::load(&path).ok()?;
+            Some((manifest.topic.id, path))
+        })
+        .collect()
+}
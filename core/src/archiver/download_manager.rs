@@ -1,5 +1,6 @@
 use std::collections::hash_map::Entry;
 use std::collections::{HashMap, HashSet};
+use std::fs;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 
@@ -9,11 +10,12 @@ use tokio::sync::mpsc::Sender;
 use tokio::sync::Semaphore;
 use tracing::warn;
 
-use crate::archiver::DownloadEvent;
+use crate::archiver::{utils, DownloadEvent};
 use crate::atomic_file::AtomicFile;
 use crate::client::{Client, IntoRequestBuilderWrapped, RequestBuilderExt, ResponseExt};
 use crate::error;
 use crate::shared_promise::{shared_promise_pair, SharedPromise};
+use crate::site::Site;
 
 const OPEN_FILES_LIMIT: usize = 128;
 
@@ -21,6 +23,9 @@ pub struct DownloadManager {
     client: Client,
     downloaded_assets: Mutex<HashSet<String>>,
     downloaded_avatars: Mutex<HashMap<String, SharedPromise<PathBuf>>>,
+    downloaded_media: Mutex<HashMap<String, SharedPromise<Option<PathBuf>>>>,
+    saved_files: Mutex<HashMap<String, PathBuf>>,
+    oversized_attachments: Mutex<HashSet<String>>,
     save_to: PathBuf,
     reporter: Sender<DownloadEvent>,
     open_files_sem: Arc<Semaphore>,
@@ -33,6 +38,9 @@ impl DownloadManager {
             save_to,
             downloaded_assets: Mutex::new(HashSet::new()),
             downloaded_avatars: Mutex::new(HashMap::new()),
+            downloaded_media: Mutex::new(HashMap::new()),
+            saved_files: Mutex::new(HashMap::new()),
+            oversized_attachments: Mutex::new(HashSet::new()),
             reporter,
             open_files_sem: Arc::new(Semaphore::new(OPEN_FILES_LIMIT)),
         }
@@ -40,6 +48,36 @@ impl DownloadManager {
 }
 
 impl DownloadManager {
+    pub fn site(&self) -> &Site {
+        self.client.site()
+    }
+    /// Files saved by this manager, keyed by their original URLs. Paths are relative to the
+    /// archive root.
+    pub fn saved_files(&self) -> HashMap<String, PathBuf> {
+        self.saved_files.lock().unwrap().clone()
+    }
+    /// Fetch a text resource, e.g. a stylesheet, to be rewritten before it's saved with
+    /// [`Self::save_text`].
+    pub async fn fetch_text(&self, from: &str) -> error::Result<String> {
+        self.reporter.send(DownloadEvent::ResourceTotalInc).await?;
+        let text = self
+            .client
+            .with(self.client.get(from), |req| async move {
+                Ok(req.send().await?.text().await?)
+            })
+            .await?;
+        self.reporter
+            .send(DownloadEvent::ResourceDownloadedInc)
+            .await?;
+        Ok(text)
+    }
+    /// Save a text resource fetched from given url into `resources/`.
+    pub fn save_text(&self, from: String, filename: &str, content: &str) -> error::Result<()> {
+        let relative_path = PathBuf::from("resources").join(filename);
+        fs::write(self.save_to.join(&relative_path), content)?;
+        self.saved_files.lock().unwrap().insert(from, relative_path);
+        Ok(())
+    }
     pub async fn download_asset(
         &self,
         from: String,
@@ -52,12 +90,13 @@ impl DownloadManager {
 
         self.reporter.send(DownloadEvent::ResourceTotalInc).await?;
 
-        let save_path = self.save_to.join("resources").join(filename);
+        let relative_path = PathBuf::from("resources").join(filename);
+        let save_path = self.save_to.join(&relative_path);
 
         if !save_path.exists() {
             let req = self
                 .client
-                .get(from)
+                .get(&from)
                 .into_request_builder_wrapped()
                 .pipe(|req| {
                     if bypass_limit {
@@ -87,11 +126,69 @@ impl DownloadManager {
                 .await?;
         }
 
+        self.saved_files.lock().unwrap().insert(from, relative_path);
         self.reporter
             .send(DownloadEvent::ResourceDownloadedInc)
             .await?;
         Ok(())
     }
+    /// Download an attachment unless it's larger than `size_limit` bytes, which is told by the
+    /// `Content-Length` header.
+    ///
+    /// Returns whether the attachment is saved. Attachments without a `Content-Length` are always
+    /// saved.
+    pub async fn download_attachment(
+        &self,
+        from: String,
+        filename: &str,
+        size_limit: Option<u64>,
+    ) -> error::Result<bool> {
+        if !self.downloaded_assets.lock().unwrap().insert(from.clone()) {
+            return Ok(!self.oversized_attachments.lock().unwrap().contains(&from));
+        }
+
+        self.reporter.send(DownloadEvent::ResourceTotalInc).await?;
+
+        let relative_path = PathBuf::from("resources").join(filename);
+        let save_path = self.save_to.join(&relative_path);
+
+        let saved = save_path.exists()
+            || self
+                .client
+                .with(self.client.get(&from), move |req| {
+                    let save_path = save_path.clone();
+                    let open_files_sem = self.open_files_sem.clone();
+                    async move {
+                        let resp = req.send().await?;
+                        if let (Some(len), Some(limit)) = (resp.content_length(), size_limit) {
+                            if len > limit {
+                                return Ok(false);
+                            }
+                        }
+
+                        let _guard = open_files_sem.acquire().await.expect("semaphore closed");
+                        let file = AtomicFile::new(&save_path).tap_err(|e| {
+                            warn!(?save_path, ?e, "[download_attachment] atomic_file_create");
+                        })?;
+
+                        resp.bytes_to_atomic_file(file).await.tap_err(|e| {
+                            warn!(?save_path, ?e, "[download_attachment] atomic_file_write");
+                        })?;
+                        Ok(true)
+                    }
+                })
+                .await?;
+
+        if saved {
+            self.saved_files.lock().unwrap().insert(from, relative_path);
+        } else {
+            self.oversized_attachments.lock().unwrap().insert(from);
+        }
+        self.reporter
+            .send(DownloadEvent::ResourceDownloadedInc)
+            .await?;
+        Ok(saved)
+    }
     pub async fn download_avatar(&self, from: String, filename: &str) -> error::Result<PathBuf> {
         let filename = PathBuf::from(filename);
         let relative_path = PathBuf::from("resources").join(&filename);
@@ -112,7 +209,7 @@ impl DownloadManager {
                 self.reporter.send(DownloadEvent::ResourceTotalInc).await?;
 
                 if !save_path.exists() {
-                    let url = format!("https://shuiyuan.sjtu.edu.cn{from}");
+                    let url = self.site().absolute_url(&from);
                     let req = self.client.get(url);
                     self.client
                         .with(req, move |req| {
@@ -145,6 +242,10 @@ impl DownloadManager {
                 }
 
                 swear.fulfill(relative_path.clone());
+                self.saved_files
+                    .lock()
+                    .unwrap()
+                    .insert(from, relative_path.clone());
 
                 self.reporter
                     .send(DownloadEvent::ResourceDownloadedInc)
@@ -162,4 +263,108 @@ impl DownloadManager {
             ),
         }
     }
+    /// Download a media file whose type is unknown from its url, naming it after `stem` and its
+    /// `Content-Type`.
+    ///
+    /// Returns the path relative to the archive root, or `None` if it's not an image, a video or an
+    /// audio.
+    pub async fn download_media(&self, from: String, stem: &str) -> error::Result<Option<PathBuf>> {
+        #[allow(clippy::significant_drop_in_scrutinee)]
+        let swear_or_promise = match self.downloaded_media.lock().unwrap().entry(from.clone()) {
+            Entry::Occupied(e) => Err(e.get().clone()),
+            Entry::Vacant(e) => {
+                let (swear, promise) = shared_promise_pair();
+                e.insert(promise);
+                Ok(swear)
+            }
+        };
+
+        match swear_or_promise {
+            Ok(swear) => {
+                self.reporter.send(DownloadEvent::ResourceTotalInc).await?;
+
+                let saved = self.saved_files.lock().unwrap().get(&from).cloned();
+                if let Some(relative_path) = saved.or_else(|| self.existing_media(stem)) {
+                    swear.fulfill(Some(relative_path.clone()));
+                    self.saved_files
+                        .lock()
+                        .unwrap()
+                        .insert(from, relative_path.clone());
+                    self.reporter
+                        .send(DownloadEvent::ResourceDownloadedInc)
+                        .await?;
+                    return Ok(Some(relative_path));
+                }
+
+                let req = self.client.get(&from);
+                let relative_path = self
+                    .client
+                    .with(req, move |req| {
+                        let open_files_sem = self.open_files_sem.clone();
+                        async move {
+                            let resp = req.send().await?;
+                            let Some(ext) = resp
+                                .headers()
+                                .get(CONTENT_TYPE)
+                                .and_then(|content_type| content_type.to_str().ok())
+                                .and_then(utils::media_extension)
+                            else {
+                                return Ok(None);
+                            };
+                            let relative_path =
+                                PathBuf::from("resources").join(format!("{stem}.{ext}"));
+                            let save_path = self.save_to.join(&relative_path);
+
+                            if !save_path.exists() {
+                                let _guard =
+                                    open_files_sem.acquire().await.expect("semaphore closed");
+                                let file = AtomicFile::new(&save_path).tap_err(|e| {
+                                    warn!(?save_path, ?e, "[download_media] atomic_file_create");
+                                })?;
+
+                                resp.bytes_to_atomic_file(file).await.tap_err(|e| {
+                                    warn!(?save_path, ?e, "[download_media] atomic_file_write");
+                                })?;
+                            }
+                            Ok(Some(relative_path))
+                        }
+                    })
+                    .await?;
+
+                swear.fulfill(relative_path.clone());
+                if let Some(relative_path) = &relative_path {
+                    self.saved_files
+                        .lock()
+                        .unwrap()
+                        .insert(from, relative_path.clone());
+                }
+
+                self.reporter
+                    .send(DownloadEvent::ResourceDownloadedInc)
+                    .await?;
+                Ok(relative_path)
+            }
+            Err(promise) => Ok(promise
+                .recv()
+                .await
+                .tap_none(|| {
+                    warn!("Promise not fulfilled which indicates an error in another task.");
+                })
+                .flatten()),
+        }
+    }
+
+    /// Find a media file named `{stem}.*` saved by a previous run, whose extension isn't known
+    /// until its `Content-Type` is fetched.
+    fn existing_media(&self, stem: &str) -> Option<PathBuf> {
+        fs::read_dir(self.save_to.join("resources"))
+            .ok()?
+            .filter_map(|entry| entry.ok())
+            .map(|entry| PathBuf::from(entry.file_name()))
+            .find(|filename| {
+                filename.extension().is_some()
+                    && filename.file_stem().and_then(|s| s.to_str()) == Some(stem)
+            })
+            .map(|filename| PathBuf::from("resources").join(filename))
+    }
 }
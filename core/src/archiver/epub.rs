@@ -0,0 +1,593 @@
+//! EPUB 3 output.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::Write as _;
+use std::fs;
+use std::fs::File;
+use std::io::Write as _;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use handlebars::html_escape;
+use html5ever::tendril::TendrilSink;
+use html5ever::{parse_fragment, Attribute, LocalName, Namespace, ParseOpts, QualName};
+use markup5ever_rcdom::{Handle, NodeData, RcDom};
+use uuid::Uuid;
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
+
+use crate::archiver::manifest::AssetManifest;
+use crate::archiver::utils::mime_of;
+use crate::error::Result;
+use crate::models::{Post, TopicMeta};
+use crate::site::Site;
+
+pub const EPUB_FILE: &str = "index.epub";
+
+const CONTAINER: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<container version="1.0" xmlns="urn:oasis:names:tc:opendocument:xmlns:container">
+    <rootfiles>
+        <rootfile full-path="OEBPS/content.opf" media-type="application/oebps-package+xml"/>
+    </rootfiles>
+</container>
+"#;
+
+const STYLE: &str = r"body { font-family: serif; line-height: 1.5; }
+img { max-width: 100%; }
+img.avatar, img.emoji { width: 1.2em; height: 1.2em; vertical-align: middle; }
+.post { border-top: 1px solid #ccc; padding-top: 0.5em; margin-top: 1em; }
+.post-meta { color: #666; font-size: 0.9em; }
+blockquote, aside.quote { border-left: 3px solid #ccc; margin-left: 0; padding-left: 0.8em; }
+pre { white-space: pre-wrap; }
+";
+
+const XHTML_NS: &str = "http://www.w3.org/1999/xhtml";
+
+const VOID_ELEMENTS: [&str; 13] = [
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "source", "track",
+    "wbr",
+];
+/// Image types every EPUB reader supports.
+const CORE_IMAGE_TYPES: [&str; 5] = [
+    "image/gif",
+    "image/jpeg",
+    "image/png",
+    "image/svg+xml",
+    "image/webp",
+];
+const BOOLEAN_ATTRS: [&str; 9] = [
+    "controls",
+    "autoplay",
+    "loop",
+    "muted",
+    "disabled",
+    "checked",
+    "open",
+    "hidden",
+    "allowfullscreen",
+];
+
+/// Write all posts of a topic into an EPUB file.
+///
+/// Each exported page becomes a chapter, and images in the archive are embedded. Other local
+/// resources, e.g. videos and images readers may not support, are linked to their original
+/// urls.
+pub fn write_epub(
+    site: &Site,
+    meta: &TopicMeta,
+    pages: &[&[Post]],
+    assets: &BTreeMap<String, AssetManifest>,
+    save_to: &Path,
+) -> Result<()> {
+    let page_of: HashMap<usize, usize> = pages
+        .iter()
+        .enumerate()
+        .flat_map(|(page, posts)| posts.iter().map(move |post| (post.number, page + 1)))
+        .collect();
+    let mut embedder = Embedder {
+        save_to,
+        original_urls: assets
+            .iter()
+            .map(|(url, asset)| (asset.filename.clone(), url.clone()))
+            .collect(),
+        images: BTreeSet::new(),
+    };
+
+    let mut chapters = vec![(String::from("cover.xhtml"), cover_page(site, meta))];
+    for (page, posts) in pages.iter().enumerate() {
+        let chapter = chapter_page(meta, page + 1, posts, &page_of, &mut embedder);
+        chapters.push((chapter_filename(page + 1), chapter));
+    }
+
+    let mut zip = ZipWriter::new(File::create(save_to.join(EPUB_FILE))?);
+    // The mimetype must be the first entry and stored uncompressed.
+    zip.start_file(
+        "mimetype",
+        FileOptions::default().compression_method(CompressionMethod::Stored),
+    )?;
+    zip.write_all(b"application/epub+zip")?;
+
+    let options = FileOptions::default();
+    zip.start_file("META-INF/container.xml", options)?;
+    zip.write_all(CONTAINER.as_bytes())?;
+    zip.start_file("OEBPS/content.opf", options)?;
+    zip.write_all(package_document(site, meta, &chapters, &embedder.images).as_bytes())?;
+    zip.start_file("OEBPS/nav.xhtml", options)?;
+    zip.write_all(nav_document(meta, pages).as_bytes())?;
+    zip.start_file("OEBPS/style.css", options)?;
+    zip.write_all(STYLE.as_bytes())?;
+    for (filename, content) in &chapters {
+        zip.start_file(format!("OEBPS/{filename}"), options)?;
+        zip.write_all(content.as_bytes())?;
+    }
+    for image in &embedder.images {
+        zip.start_file(format!("OEBPS/{}", image.display()), options)?;
+        zip.write_all(&fs::read(save_to.join(image))?)?;
+    }
+    zip.finish()?;
+    Ok(())
+}
+
+fn chapter_filename(page: usize) -> String {
+    format!("page_{page}.xhtml")
+}
+
+/// Percent-encode a path in the package for use in hrefs, keeping separators.
+fn href(path: &str) -> String {
+    path.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' => {
+                char::from(b).to_string()
+            }
+            _ => format!("%{b:02X}"),
+        })
+        .collect()
+}
+
+fn xhtml(title: &str, body: &str) -> String {
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE html>
+<html xmlns="http://www.w3.org/1999/xhtml" xmlns:epub="http://www.idpf.org/2007/ops" lang="zh-CN" xml:lang="zh-CN">
+<head>
+<meta charset="UTF-8"/>
+<title>{}</title>
+<link rel="stylesheet" type="text/css" href="style.css"/>
+</head>
+<body>
+{body}
+</body>
+</html>
+"#,
+        html_escape(title)
+    )
+}
+
+fn cover_page(site: &Site, meta: &TopicMeta) -> String {
+    let mut body = format!("<h1>{}</h1>\n", html_escape(&meta.title));
+    if !meta.categories.is_empty() {
+        let categories: Vec<_> = meta
+            .categories
+            .iter()
+            .map(|c| html_escape(&c.name))
+            .collect();
+        writeln!(body, "<p>分类：{}</p>", categories.join(" / ")).unwrap();
+    }
+    if !meta.tags.is_empty() {
+        let tags: Vec<_> = meta.tags.iter().map(|t| html_escape(t)).collect();
+        writeln!(body, "<p>标签：{}</p>", tags.join(", ")).unwrap();
+    }
+    if !meta.description.is_empty() {
+        writeln!(
+            body,
+            "<blockquote><p>{}</p></blockquote>",
+            html_escape(meta.description.trim())
+        )
+        .unwrap();
+    }
+    writeln!(
+        body,
+        "<p>原帖：{url}</p>\n<p>由 shuiyuan-archiver {} 存档</p>",
+        env!("CARGO_PKG_VERSION"),
+        url = html_escape(&site.url(&format!("/t/topic/{}", meta.id))),
+    )
+    .unwrap();
+    xhtml(&meta.title, &body)
+}
+
+fn chapter_page(
+    meta: &TopicMeta,
+    page: usize,
+    posts: &[Post],
+    page_of: &HashMap<usize, usize>,
+    embedder: &mut Embedder,
+) -> String {
+    let mut body = format!("<h2>第 {page} 页</h2>\n");
+    for post in posts {
+        writeln!(body, r#"<section class="post" id="post_{}">"#, post.number).unwrap();
+        body.push_str(r#"<p class="post-meta">"#);
+        if let Some(avatar) = post.avatar.as_ref().and_then(|a| embedder.embed(a)) {
+            write!(body, r#"<img class="avatar" src="{avatar}" alt=""/> "#).unwrap();
+        }
+        write!(body, "<strong>{}</strong>", html_escape(&post.username)).unwrap();
+        if !post.name.is_empty() {
+            write!(body, " ({})", html_escape(&post.name)).unwrap();
+        }
+        write!(body, " · {} · #{}", post.created_at_display, post.number).unwrap();
+        if let Some(reply_to) = post.reply_to {
+            match page_of.get(&reply_to) {
+                Some(&reply_page) => write!(
+                    body,
+                    r##" · 回复 <a href="{}#post_{reply_to}">#{reply_to}</a>"##,
+                    chapter_filename(reply_page)
+                ),
+                None => write!(body, " · 回复 #{reply_to}"),
+            }
+            .unwrap();
+        }
+        body.push_str("</p>\n");
+
+        writeln!(
+            body,
+            "<div class=\"post-content\">{}</div>",
+            embedder.xhtml_of(&post.content)
+        )
+        .unwrap();
+
+        if !post.emojis.is_empty() || post.likes > 0 {
+            body.push_str(r#"<p class="post-meta">"#);
+            for (emoji, count) in &post.emojis {
+                if let Some(src) = embedder.embed(&Path::new("resources").join(emoji)) {
+                    write!(body, r#"<img class="emoji" src="{src}" alt=""/> {count} "#).unwrap();
+                }
+            }
+            if post.likes > 0 {
+                write!(body, "{} 个赞", post.likes).unwrap();
+            }
+            body.push_str("</p>\n");
+        }
+        body.push_str("</section>\n");
+    }
+    xhtml(&format!("{} | 第 {page} 页", meta.title), &body)
+}
+
+fn nav_document(meta: &TopicMeta, pages: &[&[Post]]) -> String {
+    let mut body = String::from(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>目录</h1>\n<ol>\n\
+         <li><a href=\"cover.xhtml\">封面</a></li>\n",
+    );
+    for (page, posts) in pages.iter().enumerate() {
+        let filename = href(&chapter_filename(page + 1));
+        writeln!(
+            body,
+            "<li><a href=\"{filename}\">第 {} 页</a>\n<ol>",
+            page + 1
+        )
+        .unwrap();
+        for post in *posts {
+            writeln!(
+                body,
+                "<li><a href=\"{filename}#post_{n}\">#{n} {}</a></li>",
+                html_escape(&post.username),
+                n = post.number
+            )
+            .unwrap();
+        }
+        body.push_str("</ol>\n</li>\n");
+    }
+    body.push_str("</ol>\n</nav>");
+    xhtml(&meta.title, &body)
+}
+
+fn package_document(
+    site: &Site,
+    meta: &TopicMeta,
+    chapters: &[(String, String)],
+    images: &BTreeSet<PathBuf>,
+) -> String {
+    let source = site.url(&format!("/t/topic/{}", meta.id));
+    let identifier = Uuid::new_v5(&Uuid::NAMESPACE_URL, source.as_bytes());
+
+    let mut metadata = format!(
+        "<dc:identifier id=\"uid\">urn:uuid:{identifier}</dc:identifier>\n\
+         <dc:title>{}</dc:title>\n\
+         <dc:language>zh-CN</dc:language>\n\
+         <dc:publisher>{}</dc:publisher>\n\
+         <dc:source>{}</dc:source>\n\
+         <meta property=\"dcterms:modified\">{}</meta>\n",
+        html_escape(&meta.title),
+        html_escape(&site.name),
+        html_escape(&source),
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    );
+    if !meta.description.is_empty() {
+        writeln!(
+            metadata,
+            "<dc:description>{}</dc:description>",
+            html_escape(meta.description.trim())
+        )
+        .unwrap();
+    }
+    for subject in meta.categories.iter().map(|c| &c.name).chain(&meta.tags) {
+        writeln!(
+            metadata,
+            "<dc:subject>{}</dc:subject>",
+            html_escape(subject)
+        )
+        .unwrap();
+    }
+
+    let mut manifest = String::from(
+        "<item id=\"nav\" href=\"nav.xhtml\" media-type=\"application/xhtml+xml\" properties=\"nav\"/>\n\
+         <item id=\"style\" href=\"style.css\" media-type=\"text/css\"/>\n",
+    );
+    let mut spine = String::new();
+    for (idx, (filename, _)) in chapters.iter().enumerate() {
+        writeln!(
+            manifest,
+            "<item id=\"chapter_{idx}\" href=\"{}\" media-type=\"application/xhtml+xml\"/>",
+            href(filename)
+        )
+        .unwrap();
+        writeln!(spine, "<itemref idref=\"chapter_{idx}\"/>").unwrap();
+    }
+    for (idx, image) in images.iter().enumerate() {
+        writeln!(
+            manifest,
+            "<item id=\"image_{idx}\" href=\"{}\" media-type=\"{}\"/>",
+            href(&image.display().to_string()),
+            mime_of(image)
+        )
+        .unwrap();
+    }
+
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8"?>
+<package xmlns="http://www.idpf.org/2007/opf" version="3.0" unique-identifier="uid" xml:lang="zh-CN">
+<metadata xmlns:dc="http://purl.org/dc/elements/1.1/">
+{metadata}</metadata>
+<manifest>
+{manifest}</manifest>
+<spine>
+{spine}</spine>
+</package>
+"#
+    )
+}
+
+/// Collect images to embed, and convert cooked content into XHTML.
+struct Embedder<'a> {
+    save_to: &'a Path,
+    original_urls: HashMap<PathBuf, String>,
+    /// Embedded images, relative to the archive root.
+    images: BTreeSet<PathBuf>,
+}
+
+impl Embedder<'_> {
+    /// Embed a local image, returning its reference in chapters.
+    ///
+    /// Only images of core media types are embedded, because readers aren't required to support
+    /// others, e.g. AVIF and HEIC.
+    fn embed(&mut self, path: &Path) -> Option<String> {
+        if !CORE_IMAGE_TYPES.contains(&mime_of(path)) || !self.save_to.join(path).exists() {
+            return None;
+        }
+        self.images.insert(path.to_path_buf());
+        Some(href(&path.display().to_string()))
+    }
+
+    /// Parse cooked content as HTML and serialize it as XHTML.
+    fn xhtml_of(&mut self, html: &str) -> String {
+        let dom = parse_fragment(
+            RcDom::default(),
+            ParseOpts::default(),
+            QualName::new(None, Namespace::from(XHTML_NS), LocalName::from("body")),
+            vec![],
+        )
+        .one(html);
+        let mut xhtml = String::new();
+        // Nodes of a fragment are put under an `html` element.
+        for root in dom.document.children.borrow().iter() {
+            for node in root.children.borrow().iter() {
+                self.write_node(node, XHTML_NS, &mut xhtml);
+            }
+        }
+        xhtml
+    }
+
+    fn write_node(&mut self, node: &Handle, parent_ns: &str, xhtml: &mut String) {
+        match &node.data {
+            NodeData::Text { contents } => xhtml.push_str(&xml_escape(&contents.borrow())),
+            NodeData::Element { name, attrs, .. } => {
+                let tag = &*name.local;
+                if matches!(
+                    tag,
+                    "script" | "style" | "noscript" | "iframe" | "svg" | "video" | "audio"
+                ) {
+                    return;
+                }
+                let children = node.children.borrow();
+                if !is_xml_name(tag) {
+                    // Elements named in a way XML doesn't allow are unwrapped.
+                    for child in children.iter() {
+                        self.write_node(child, parent_ns, xhtml);
+                    }
+                    return;
+                }
+
+                write!(xhtml, "<{tag}").unwrap();
+                if &*name.ns != parent_ns {
+                    write!(xhtml, r#" xmlns="{}""#, xml_escape(&name.ns)).unwrap();
+                }
+                for (attr, value) in self.xhtml_attrs(&attrs.borrow()) {
+                    write!(xhtml, r#" {attr}="{}""#, xml_escape(&value)).unwrap();
+                }
+                if VOID_ELEMENTS.contains(&tag) {
+                    xhtml.push_str("/>");
+                    return;
+                }
+                xhtml.push('>');
+                for child in children.iter() {
+                    self.write_node(child, &name.ns, xhtml);
+                }
+                write!(xhtml, "</{tag}>").unwrap();
+            }
+            // Comments, doctypes and processing instructions are dropped.
+            _ => {}
+        }
+    }
+
+    /// Attributes of an element which are valid in XHTML. Local images are embedded, and other
+    /// local resources are linked to their original urls.
+    fn xhtml_attrs(&mut self, attrs: &[Attribute]) -> Vec<(String, String)> {
+        attrs
+            .iter()
+            // Responsive images are not supported by most readers.
+            .filter(|attr| {
+                attr.name.ns.is_empty()
+                    && is_xml_name(&attr.name.local)
+                    && &*attr.name.local != "srcset"
+            })
+            .filter_map(|attr| {
+                let name = attr.name.local.to_string();
+                let value = attr.value.to_string();
+                if BOOLEAN_ATTRS.contains(&name.as_str()) {
+                    return Some((name.clone(), name));
+                }
+                if !matches!(name.as_str(), "src" | "href") || !value.starts_with("resources/") {
+                    return Some((name, value));
+                }
+                let path = PathBuf::from(&value);
+                if let Some(src) = (name == "src").then(|| self.embed(&path)).flatten() {
+                    return Some((name, src));
+                }
+                self.original_urls.get(&path).map(|url| (name, url.clone()))
+            })
+            .collect()
+    }
+}
+
+/// Escape text for XML, dropping characters XML doesn't allow.
+fn xml_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\t'
+            | '\n'
+            | '\r'
+            | '\u{20}'..='\u{D7FF}'
+            | '\u{E000}'..='\u{FFFD}'
+            | '\u{10000}'.. => {
+                escaped.push(c);
+            }
+            _ => {}
+        }
+    }
+    escaped
+}
+
+/// Whether a name of element or attribute is valid in XML. Names with colons are rejected as
+/// they would be taken as namespace prefixes.
+fn is_xml_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.'))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashMap};
+    use std::fs;
+    use std::path::{Path, PathBuf};
+
+    use roxmltree::{Document, ParsingOptions};
+    use tempfile::TempDir;
+
+    use crate::models::{Post, TopicMeta};
+
+    use super::{chapter_page, href, Embedder};
+
+    fn embedder(save_to: &Path) -> Embedder<'_> {
+        Embedder {
+            save_to,
+            original_urls: HashMap::from([(
+                PathBuf::from("resources/b.avif"),
+                String::from("https://a.com/b.avif"),
+            )]),
+            images: BTreeSet::new(),
+        }
+    }
+
+    #[test]
+    fn must_convert_entities() {
+        let dir = TempDir::new().unwrap();
+        assert_eq!(
+            embedder(dir.path()).xhtml_of("a&nbsp;b &amp; c &#39; d & e &unknown;"),
+            "a\u{a0}b &amp; c ' d &amp; e &amp;unknown;"
+        );
+    }
+
+    #[test]
+    fn must_write_well_formed_chapters() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("resources")).unwrap();
+        for image in ["a.png", "b.avif"] {
+            fs::write(dir.path().join("resources").join(image), "").unwrap();
+        }
+        let mut embedder = embedder(dir.path());
+        let meta = TopicMeta {
+            id: 1,
+            title: String::from("<a> & b"),
+            description: String::new(),
+            categories: vec![],
+            tags: vec![],
+            post_ids: vec![1],
+            private: false,
+            participants: vec![],
+            raw: None,
+        };
+        let post = Post {
+            number: 1,
+            username: String::from("a&b"),
+            content: String::from(
+                r#"<p>a<br>b <img src="resources/a.png" alt=a> <img src="resources/b.avif">
+                <span @click="x" 1a="b" data-x='"'>c<math><mi>x</mi></math>
+                <video controls src="resources/c.mp4"></video><input disabled>"#,
+            ),
+            ..Post::default()
+        };
+
+        let chapter = chapter_page(&meta, 1, &[post], &HashMap::new(), &mut embedder);
+        let doc = Document::parse_with_options(
+            &chapter,
+            ParsingOptions {
+                allow_dtd: true,
+                ..ParsingOptions::default()
+            },
+        )
+        .unwrap();
+        let srcs: Vec<_> = doc
+            .descendants()
+            .filter(|node| node.has_tag_name("img"))
+            .filter_map(|node| node.attribute("src"))
+            .collect();
+        assert_eq!(srcs, ["resources/a.png", "https://a.com/b.avif"]);
+        assert_eq!(
+            embedder.images,
+            BTreeSet::from([PathBuf::from("resources/a.png")])
+        );
+    }
+
+    #[test]
+    fn must_encode_hrefs() {
+        assert_eq!(href("page_1.xhtml"), "page_1.xhtml");
+        assert_eq!(
+            href("resources/图 1&2.png"),
+            "resources/%E5%9B%BE%201%262.png"
+        );
+    }
+}
@@ -1,27 +1,35 @@
 use std::cell::RefCell;
+use std::collections::{HashMap, HashSet};
 use std::iter;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use futures::stream::FuturesUnordered;
 use futures::{stream, TryStreamExt};
-use lol_html::html_content::ContentType;
+use handlebars::html_escape;
+use lol_html::html_content::{ContentType, Element};
 use lol_html::{element, rewrite_str, RewriteStrSettings};
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::Value;
 use tap::TapFallible;
 use tracing::{error, warn};
 
 use crate::action_code::ACTION_CODE_MAP;
 use crate::archiver::download_manager::DownloadManager;
+use crate::archiver::options::{ArchiveOptions, AttachmentOptions};
 use crate::archiver::utils;
 use crate::archiver::utils::summarize;
 use crate::client::Client;
 use crate::error;
 use crate::models::{
-    Category, RespCategory, RespCooked, RespPost, RespRetort, RespTopic, TopicMeta,
+    category_path, Bookmark, Category, CategoryInfo, RespBookmarks, RespCategory, RespCooked,
+    RespCurrentUser, RespPost, RespRetort, RespSearch, RespSite, RespSiteCategory, RespTopic,
+    RespTopicList, RespUser, RespUserActions, SearchResult, TopicMeta, TopicSummary, UserAction,
+    UserProfile,
 };
 use crate::preloaded_store::PreloadedStore;
-
-const IMAGE_SUFFIXES: [&str; 4] = ["jpg", "jpeg", "gif", "png"];
-const VIDEO_SUFFIXES: [&str; 3] = ["mp4", "mov", "avi"];
+use crate::site::Site;
 
 pub async fn fetch_avatar(
     download_manager: &DownloadManager,
@@ -45,20 +53,22 @@ pub async fn fetch_emoji_from_retort(
     preloaded_store: &PreloadedStore,
     r: RespRetort,
 ) -> error::Result<(String, usize)> {
+    let site = download_manager.site();
     let filename = if let Some(emoji_path) = preloaded_store.custom_emoji(&r.emoji) {
         let filename = sanitize_filename::sanitize(emoji_path.rsplit('/').next().unwrap());
         download_manager
-            .download_asset(absolute_url(emoji_path), &filename, false)
+            .download_asset(site.absolute_url(emoji_path), &filename, false)
             .await?;
         filename
     } else {
         let filename = sanitize_filename::sanitize(format!("{}.png", r.emoji));
         let url = format!(
-            "/images/emoji/google/{}.png",
+            "/images/emoji/{}/{}.png",
+            site.emoji_set,
             utils::normalize_emoji(&r.emoji)
         );
         download_manager
-            .download_asset(absolute_url(&url), &filename, false)
+            .download_asset(site.absolute_url(&url), &filename, false)
             .await?;
         filename
     };
@@ -87,37 +97,253 @@ fn url_to_filename(url: &str) -> String {
     sanitize_filename::sanitize(new_name)
 }
 
-fn absolute_url(url: &str) -> String {
-    if url.starts_with("//") {
-        format!("https:{url}")
-    } else if url.starts_with('/') {
-        format!("https://shuiyuan.sjtu.edu.cn{url}")
-    } else {
-        url.to_string()
-    }
-}
-
+/// Download assets of given content, and rewrite their urls to local files.
+///
+/// Returns the rewritten content, and local paths of uploads keyed by their base62 sha1, which
+/// are used to resolve `upload://` short urls in raw post sources.
 pub async fn fetch_assets_of_content(
     download_manager: &DownloadManager,
     content: &str,
-    anonymous: bool,
-) -> error::Result<String> {
+    options: &ArchiveOptions,
+) -> error::Result<(String, HashMap<String, String>)> {
     let ExtractAssetResult {
         urls,
+        untyped,
+        mut uploads,
+        rewritten_content,
+    } = extract_asset_url(
+        content,
+        &download_manager.site().base_url,
+        options.anonymous,
+        !options.previews_only,
+    );
+    let rewritten_content = fetch_attachments_of_content(
+        download_manager,
         rewritten_content,
-    } = extract_asset_url(content, anonymous);
+        &options.attachments,
+        &mut uploads,
+    )
+    .await?;
 
     let futs: FuturesUnordered<_> = urls
         .into_iter()
         .map(|url| async move {
             download_manager
-                .download_asset(absolute_url(&url), &url_to_filename(&url), false)
+                .download_asset(
+                    download_manager.site().absolute_url(&url),
+                    &url_to_filename(&url),
+                    false,
+                )
                 .await
         })
         .collect();
     futs.try_collect().await?;
 
-    Ok(rewritten_content)
+    let futs: FuturesUnordered<_> = untyped
+        .into_iter()
+        .collect::<HashSet<_>>()
+        .into_iter()
+        .map(|url| async move {
+            let local = download_manager
+                .download_media(
+                    download_manager.site().absolute_url(&url),
+                    &url_to_filename(&url),
+                )
+                .await?;
+            Ok::<_, error::Error>(local.and_then(|local| {
+                let filename = local.file_name()?.to_string_lossy().into_owned();
+                Some((url, format!("resources/{filename}")))
+            }))
+        })
+        .collect();
+    let typed: HashMap<String, String> = futs
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+    let rewritten_content = if typed.is_empty() && !rewritten_content.contains("data-video-src") {
+        rewritten_content
+    } else {
+        rewrite_typed_media(
+            &rewritten_content,
+            download_manager.site(),
+            &typed,
+            &mut uploads,
+        )?
+    };
+
+    Ok((rewritten_content, uploads))
+}
+
+/// Point media whose types are found out when downloading to local files, and turn video
+/// placeholders, which are only played with scripts of the site, into videos.
+fn rewrite_typed_media(
+    content: &str,
+    site: &Site,
+    typed: &HashMap<String, String>,
+    uploads: &mut HashMap<String, String>,
+) -> error::Result<String> {
+    let uploads = RefCell::new(uploads);
+    let media_rule = element!("img, source, video, audio", |el| {
+        for attr in ["src", "poster"] {
+            if let Some(local) = el.get_attribute(attr).and_then(|url| typed.get(&url)) {
+                el.set_attribute(attr, local)?;
+                if let Some(sha1) = el.get_attribute("data-base62-sha1") {
+                    uploads.borrow_mut().insert(sha1, local.clone());
+                }
+            }
+        }
+        Ok(())
+    });
+    let placeholder_rule = element!("[data-video-src]", |el| {
+        let resolve = |attr: &str| {
+            el.get_attribute(attr).map(|url| {
+                typed
+                    .get(&url)
+                    .cloned()
+                    .unwrap_or_else(|| site.absolute_url(&url))
+            })
+        };
+        let src = resolve("data-video-src").expect("has data-video-src");
+        let poster = resolve("data-thumbnail-src")
+            .map(|poster| format!(r#" poster="{}""#, html_escape(&poster)))
+            .unwrap_or_default();
+        let mime = utils::mime_of(Path::new(&src));
+        let source_type = if mime.starts_with("video/") {
+            format!(r#" type="{mime}""#)
+        } else {
+            String::new()
+        };
+        el.replace(
+            &format!(
+                r#"<video controls preload="metadata"{poster}><source src="{}"{source_type}></video>"#,
+                html_escape(&src)
+            ),
+            ContentType::Html,
+        );
+        Ok(())
+    });
+    Ok(rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![media_rule, placeholder_rule],
+            ..RewriteStrSettings::default()
+        },
+    )?)
+}
+
+/// Download attachments linked from given content, i.e. uploaded files other than media, and
+/// point their links to local files. Attachments not downloaded are still linked to the site.
+async fn fetch_attachments_of_content(
+    download_manager: &DownloadManager,
+    content: String,
+    options: &AttachmentOptions,
+    uploads: &mut HashMap<String, String>,
+) -> error::Result<String> {
+    if !options.download {
+        return Ok(content);
+    }
+    let urls = extract_attachment_urls(&content, &download_manager.site().base_url, options);
+    if urls.is_empty() {
+        return Ok(content);
+    }
+
+    let futs: FuturesUnordered<_> = urls
+        .into_iter()
+        .map(|url| async move {
+            let saved = download_manager
+                .download_attachment(
+                    download_manager.site().absolute_url(&url),
+                    &url_to_filename(&url),
+                    options.size_limit(),
+                )
+                .await?;
+            Ok::<_, error::Error>(saved.then(|| {
+                let local = format!("resources/{}", url_to_filename(&url));
+                (url, local)
+            }))
+        })
+        .collect();
+    let saved: HashMap<String, String> = futs
+        .try_collect::<Vec<_>>()
+        .await?
+        .into_iter()
+        .flatten()
+        .collect();
+
+    // Short urls are named after base62 sha1 of the uploads.
+    for (url, local) in &saved {
+        if let Some(name) = url.split_once("/uploads/short-url/").map(|(_, name)| name) {
+            let sha1 = name.split_once('.').map_or(name, |(sha1, _)| sha1);
+            uploads.insert(sha1.to_string(), local.clone());
+        }
+    }
+
+    let rule = element!("a.attachment[href]", |el| {
+        let href = el.get_attribute("href").expect("has href");
+        if let Some(local) = saved.get(&href) {
+            el.set_attribute("href", local)?;
+        }
+        Ok(())
+    });
+    Ok(rewrite_str(
+        &content,
+        RewriteStrSettings {
+            element_content_handlers: vec![rule],
+            ..RewriteStrSettings::default()
+        },
+    )?)
+}
+
+/// Urls of attachments to download in given content, which are uploaded to the site itself.
+fn extract_attachment_urls(
+    content: &str,
+    base_url: &str,
+    options: &AttachmentOptions,
+) -> Vec<String> {
+    let urls = RefCell::new(vec![]);
+    let rule = element!("a.attachment[href]", |el| {
+        let href = el.get_attribute("href").expect("has href");
+        let path = href.strip_prefix(base_url).unwrap_or(&href);
+        if path.starts_with("/uploads/")
+            && options.accepts(url_extension(path))
+            && !urls.borrow().contains(&href)
+        {
+            urls.borrow_mut().push(href);
+        }
+        Ok(())
+    });
+    let _ = rewrite_str(
+        content,
+        RewriteStrSettings {
+            element_content_handlers: vec![rule],
+            ..RewriteStrSettings::default()
+        },
+    );
+    urls.into_inner()
+}
+
+/// Fetch the raw source of a post.
+///
+/// Returns `None` if the raw source is not accessible.
+pub async fn fetch_raw(client: &Client, post_id: usize) -> error::Result<Option<String>> {
+    let url = client.site().url(&format!("/posts/{post_id}/raw"));
+    client
+        .with(client.get(url), |req| async move {
+            let resp = req.send().await?;
+            if matches!(resp.status(), StatusCode::NOT_FOUND | StatusCode::FORBIDDEN) {
+                return Ok(None);
+            }
+            Ok(Some(resp.error_for_status()?.text().await?))
+        })
+        .await
+}
+
+/// Fetch the unmodified response of a topic.
+pub async fn fetch_topic_raw(client: &Client, topic_id: u32) -> error::Result<Value> {
+    let url = client.site().url(&format!("/t/{topic_id}.json"));
+    client.send_json(client.get(url)).await
 }
 
 /// Fetch topic meta data.
@@ -126,26 +352,45 @@ pub async fn fetch_assets_of_content(
 ///
 /// Returns error if failed to fetch topic meta or failed to fetch category names.
 pub async fn fetch_topic_meta(client: &Client, topic_id: u32) -> error::Result<TopicMeta> {
-    let url = format!("https://shuiyuan.sjtu.edu.cn/t/{topic_id}.json");
-    let resp: RespTopic = client.send_json(client.get(url)).await?;
+    let raw = fetch_topic_raw(client, topic_id).await?;
+    let resp = RespTopic::deserialize(&raw)?;
 
     let first_post = resp.post_stream.posts.first().expect("at least one post");
     let description = utils::summarize(&first_post.cooked);
 
+    let categories = match resp.category_id {
+        Some(category_id) => categories_from_id(client, category_id).await?,
+        None => vec![],
+    };
+    let participants = resp
+        .details
+        .map(|details| {
+            details
+                .allowed_users
+                .into_iter()
+                .map(|user| user.username)
+                .chain(details.allowed_groups.into_iter().map(|group| group.name))
+                .collect()
+        })
+        .unwrap_or_default();
+
     Ok(TopicMeta {
         id: topic_id,
         title: resp.title,
         description,
-        categories: categories_from_id(client, resp.category_id).await?,
+        categories,
         tags: resp.tags,
         post_ids: resp.post_stream.stream.expect("exists"),
+        private: resp.archetype == "private_message",
+        participants,
+        raw: Some(Arc::new(raw)),
     })
 }
 
 /// Get category names from a leaf category id.
 async fn categories_from_id(client: &Client, leaf_id: usize) -> error::Result<Vec<Category>> {
     stream::try_unfold(leaf_id, |id| async move {
-        let url = format!("https://shuiyuan.sjtu.edu.cn/c/{id}/show.json");
+        let url = client.site().url(&format!("/c/{id}/show.json"));
         let resp: RespCategory = client.send_json(client.get(url)).await?;
 
         let yielded = resp.category.inner;
@@ -156,6 +401,227 @@ async fn categories_from_id(client: &Client, leaf_id: usize) -> error::Result<Ve
     .await
 }
 
+/// Fetch name and slug of a category.
+///
+/// # Errors
+///
+/// Returns error if the category doesn't exist or is not accessible.
+pub async fn fetch_category(client: &Client, category_id: usize) -> error::Result<CategoryInfo> {
+    let url = client.site().url(&format!("/c/{category_id}/show.json"));
+    let resp: RespCategory = client.send_json(client.get(url)).await?;
+    Ok(CategoryInfo {
+        id: category_id,
+        name: resp.category.inner.name,
+        slug: resp.category.slug,
+        description: resp.category.description_text,
+    })
+}
+
+/// Fetch all topics in a category, including those in its subcategories.
+///
+/// # Errors
+///
+/// Returns error if failed to fetch categories of the site, or any page of the listings.
+pub async fn fetch_category_topics(
+    client: &Client,
+    category: &CategoryInfo,
+) -> error::Result<Vec<TopicSummary>> {
+    let url = client.site().url("/site.json");
+    let site: RespSite = client.send_json(client.get(url)).await?;
+
+    let mut seen = HashSet::new();
+    let mut topics = vec![];
+    let categories = iter::once((category.slug.as_str(), category.id))
+        .chain(subcategories(category.id, &site.categories));
+    for (slug, id) in categories {
+        let path = format!("{}.json", category_path(slug, id));
+        let list = fetch_topic_list(client, &path).await?;
+        // Parent listings may include topics of subcategories.
+        topics.extend(list.into_iter().filter(|topic| seen.insert(topic.id)));
+    }
+    Ok(topics)
+}
+
+/// Slugs and ids of all descendants of a category, parents first.
+fn subcategories(root: usize, categories: &[RespSiteCategory]) -> Vec<(&str, usize)> {
+    let mut found = vec![];
+    let mut parents = vec![root];
+    while let Some(parent) = parents.pop() {
+        for category in categories
+            .iter()
+            .filter(|category| category.parent_category_id == Some(parent))
+        {
+            found.push((category.slug.as_str(), category.id));
+            parents.push(category.id);
+        }
+    }
+    found
+}
+
+/// Fetch all topics with a tag.
+///
+/// # Errors
+///
+/// Returns error if failed to fetch any page of the listing.
+pub async fn fetch_tag_topics(client: &Client, tag: &str) -> error::Result<Vec<TopicSummary>> {
+    fetch_topic_list(client, &format!("/tag/{tag}.json")).await
+}
+
+/// Fetch a topic listing page by page.
+async fn fetch_topic_list(client: &Client, path: &str) -> error::Result<Vec<TopicSummary>> {
+    let mut seen = HashSet::new();
+    let mut topics = vec![];
+    for page in 0.. {
+        let req = client.get(client.site().url(path)).query(&[("page", page)]);
+        let resp: RespTopicList = client.send_json(req).await?;
+        let before = topics.len();
+        // Pinned topics may show up on every page.
+        topics.extend(
+            resp.topic_list
+                .topics
+                .into_iter()
+                .filter(|topic| seen.insert(topic.id)),
+        );
+        if resp.topic_list.more_topics_url.is_none() || topics.len() == before {
+            break;
+        }
+    }
+    Ok(topics)
+}
+
+/// Fetch all private messages in the inbox and sent box of a user, newest first.
+///
+/// # Errors
+///
+/// Returns error if the messages are not accessible, or failed to fetch any page of the listing.
+pub async fn fetch_private_messages(
+    client: &Client,
+    username: &str,
+) -> error::Result<Vec<TopicSummary>> {
+    let mut topics =
+        fetch_topic_list(client, &format!("/topics/private-messages/{username}.json")).await?;
+    let sent = fetch_topic_list(
+        client,
+        &format!("/topics/private-messages-sent/{username}.json"),
+    )
+    .await?;
+    let seen: HashSet<u32> = topics.iter().map(|topic| topic.id).collect();
+    topics.extend(sent.into_iter().filter(|topic| !seen.contains(&topic.id)));
+    topics.sort_by(|a, b| b.last_posted_at.cmp(&a.last_posted_at));
+    Ok(topics)
+}
+
+/// Fetch the profile of the user the token belongs to.
+pub async fn fetch_current_user(client: &Client) -> error::Result<UserProfile> {
+    let url = client.site().url("/session/current.json");
+    let resp: RespCurrentUser = client.send_json(client.get(url)).await?;
+    Ok(resp.current_user)
+}
+
+/// Fetch all bookmarks of a user, newest first.
+///
+/// Only bookmarks of the user the token belongs to are accessible.
+///
+/// # Errors
+///
+/// Returns error if the bookmarks are not accessible, or failed to fetch any page of them.
+pub async fn fetch_bookmarks(client: &Client, username: &str) -> error::Result<Vec<Bookmark>> {
+    let mut seen = HashSet::new();
+    let mut bookmarks: Vec<Bookmark> = vec![];
+    for page in 0.. {
+        let req = client
+            .get(client.site().url(&format!("/u/{username}/bookmarks.json")))
+            .query(&[("page", page)]);
+        let resp: RespBookmarks = client.send_json(req).await?;
+        let Some(list) = resp.user_bookmark_list else {
+            break;
+        };
+        let before = bookmarks.len();
+        bookmarks.extend(
+            list.bookmarks
+                .into_iter()
+                .filter(|bookmark| seen.insert(bookmark.id)),
+        );
+        if list.more_bookmarks_url.is_none() || bookmarks.len() == before {
+            break;
+        }
+    }
+    Ok(bookmarks)
+}
+
+/// Fetch the profile of a user.
+pub async fn fetch_user(client: &Client, username: &str) -> error::Result<UserProfile> {
+    let url = client.site().url(&format!("/u/{username}.json"));
+    let resp: RespUser = client.send_json(client.get(url)).await?;
+    Ok(resp.user)
+}
+
+/// Fetch all topics created and posts written by a user, newest first.
+pub async fn fetch_user_actions(client: &Client, username: &str) -> error::Result<Vec<UserAction>> {
+    // 4: new topic, 5: reply
+    const FILTER: &str = "4,5";
+
+    let mut actions: Vec<UserAction> = vec![];
+    loop {
+        let req = client.get(client.site().url("/user_actions.json")).query(&[
+            ("username", username),
+            ("filter", FILTER),
+            ("offset", &actions.len().to_string()),
+        ]);
+        let resp: RespUserActions = client.send_json(req).await?;
+        if resp.user_actions.is_empty() {
+            break;
+        }
+        actions.extend(resp.user_actions);
+    }
+    Ok(actions)
+}
+
+/// Fetch all topics and posts matching a search query.
+///
+/// The query may contain advanced search operators, e.g. `@username`, `#category` or `in:title`.
+///
+/// # Errors
+///
+/// Returns error if failed to fetch any page of the results.
+pub async fn fetch_search(client: &Client, query: &str) -> error::Result<SearchResult> {
+    let mut seen_topics = HashSet::new();
+    let mut seen_posts = HashSet::new();
+    let mut result = SearchResult::default();
+    for page in 1.. {
+        let req = client
+            .get(client.site().url("/search.json"))
+            .query(&[("q", query), ("page", &page.to_string())]);
+        let resp: RespSearch = client.send_json(req).await?;
+        let before = result.posts.len();
+        result.topics.extend(
+            resp.topics
+                .into_iter()
+                .filter(|topic| seen_topics.insert(topic.id)),
+        );
+        result.posts.extend(
+            resp.posts
+                .into_iter()
+                .filter(|post| seen_posts.insert(post.id)),
+        );
+        let more = resp
+            .grouped_search_result
+            .and_then(|grouped| grouped.more_full_page_results)
+            .unwrap_or_default();
+        if !more || result.posts.len() == before {
+            break;
+        }
+    }
+    Ok(result)
+}
+
+/// Fetch ids of all posts in a topic.
+pub async fn fetch_post_stream(client: &Client, topic_id: u32) -> error::Result<Vec<u32>> {
+    let url = client.site().url(&format!("/t/{topic_id}.json"));
+    let resp: RespTopic = client.send_json(client.get(url)).await?;
+    Ok(resp.post_stream.stream.unwrap_or_default())
+}
+
 /// Reveal hidden posts and convert system messages.
 pub async fn fetch_special_post(client: &Client, post: RespPost) -> error::Result<RespPost> {
     if let Some((_, system_msg)) = post
@@ -163,12 +629,28 @@ pub async fn fetch_special_post(client: &Client, post: RespPost) -> error::Resul
         .as_ref()
         .and_then(|code| ACTION_CODE_MAP.iter().find(|(c, _)| c == code))
     {
-        Ok(RespPost {
-            cooked: format!("<p>系统消息：{system_msg}</p>"),
-            ..post
-        })
+        // Mentions are masked in anonymous mode like those in posts.
+        let cooked = match (post.action_code.as_deref(), &post.action_code_who) {
+            (Some("user_left"), Some(who)) => {
+                format!(
+                    r#"<p>系统消息：<a class="mention" href="/u/{who}">@{who}</a> {system_msg}</p>"#
+                )
+            }
+            (Some("invited_user" | "removed_user"), Some(who)) => {
+                format!(
+                    r#"<p>系统消息：{system_msg} <a class="mention" href="/u/{who}">@{who}</a></p>"#
+                )
+            }
+            (Some("invited_group" | "removed_group"), Some(who)) => {
+                format!("<p>系统消息：{system_msg} {}</p>", html_escape(who))
+            }
+            _ => format!("<p>系统消息：{system_msg}</p>"),
+        };
+        Ok(RespPost { cooked, ..post })
     } else if post.cooked_hidden {
-        let url = format!("https://shuiyuan.sjtu.edu.cn/posts/{}/cooked.json", post.id);
+        let url = client
+            .site()
+            .url(&format!("/posts/{}/cooked.json", post.id));
         let resp: RespCooked = client.send_json(client.get(url)).await?;
         Ok(RespPost {
             cooked: format!(r#"<p style="color: gray">被折叠的内容</p>{}"#, resp.cooked),
@@ -248,24 +730,73 @@ fn rewrite_srcset(attr: &str, mut rewrite: impl FnMut(&str) -> Option<String>) -
         })
 }
 
-fn filter_media(url: &str) -> bool {
+fn url_extension(url: &str) -> &str {
     let no_query = url.rsplit_once('?').map_or(url, |(url, _)| url);
     let no_fragment = no_query.rsplit_once('#').map_or(no_query, |(url, _)| url);
     let filename = no_fragment
         .rsplit_once('/')
         .map_or(no_fragment, |(_, filename)| filename);
-    let ext = filename.rsplit_once('.').map_or(filename, |(_, ext)| ext);
-    VIDEO_SUFFIXES.iter().any(|&s| ext.eq_ignore_ascii_case(s))
-        || IMAGE_SUFFIXES.iter().any(|&s| ext.eq_ignore_ascii_case(s))
+    filename.rsplit_once('.').map_or(filename, |(_, ext)| ext)
+}
+
+fn filter_media(url: &str) -> bool {
+    utils::is_media_extension(url_extension(url))
+}
+
+/// Whether given url is uploaded to the site itself.
+fn is_upload(url: &str, base_url: &str) -> bool {
+    url.strip_prefix(base_url)
+        .unwrap_or(url)
+        .starts_with("/uploads/")
+}
+
+/// Point a media attribute to a local file if the media type is known from its extension.
+/// Otherwise, if it's an upload as told by its url or upload metadata, collect it so that its type
+/// is found out when downloading.
+///
+/// Returns the local file if the attribute is rewritten.
+fn rewrite_media_attr(
+    el: &mut Element,
+    attr: &str,
+    base_url: &str,
+    urls: &RefCell<Vec<String>>,
+    untyped: &RefCell<Vec<String>>,
+) -> Result<Option<String>, Box<dyn std::error::Error + Send + Sync>> {
+    let Some(url) = el.get_attribute(attr) else {
+        return Ok(None);
+    };
+    if filter_media(&url) {
+        let local = format!("resources/{}", url_to_filename(&url));
+        el.set_attribute(attr, &local)?;
+        urls.borrow_mut().push(url);
+        return Ok(Some(local));
+    }
+    if is_upload(&url, base_url)
+        || el.has_attribute("data-base62-sha1")
+        || el.has_attribute("data-orig-src")
+    {
+        untyped.borrow_mut().push(url);
+    }
+    Ok(None)
 }
 
 struct ExtractAssetResult {
     urls: Vec<String>,
+    /// Urls of uploaded media whose types are unknown until downloaded.
+    untyped: Vec<String>,
+    uploads: HashMap<String, String>,
     rewritten_content: String,
 }
 
-fn extract_asset_url(content: &str, anonymous: bool) -> ExtractAssetResult {
+fn extract_asset_url(
+    content: &str,
+    base_url: &str,
+    anonymous: bool,
+    originals: bool,
+) -> ExtractAssetResult {
     let urls = RefCell::new(vec![]);
+    let untyped = RefCell::new(vec![]);
+    let uploads = RefCell::new(HashMap::new());
 
     let a_rule = element!("a", |el| {
         if let Some(url) = el.get_attribute("src") {
@@ -291,18 +822,18 @@ fn extract_asset_url(content: &str, anonymous: bool) -> ExtractAssetResult {
         Ok(())
     });
 
-    let img_n_source = if anonymous {
-        "img:not(.avatar), source"
+    let media = if anonymous {
+        "img:not(.avatar), source, video, audio"
     } else {
-        "img, source"
+        "img, source, video, audio"
     };
-    let img_rule = element!(img_n_source, |el| {
-        if let Some(url) = el.get_attribute("src") {
-            if filter_media(&url) {
-                el.set_attribute("src", &format!("resources/{}", url_to_filename(&url)))?;
-                urls.borrow_mut().push(url);
+    let img_rule = element!(media, |el| {
+        if let Some(local) = rewrite_media_attr(el, "src", base_url, &urls, &untyped)? {
+            if let Some(sha1) = el.get_attribute("data-base62-sha1") {
+                uploads.borrow_mut().insert(sha1, local);
             }
         }
+        rewrite_media_attr(el, "poster", base_url, &urls, &untyped)?;
         if let Some(srcset) = el.get_attribute("srcset") {
             let mut srcset_imgs = vec![];
             if let Some(srcset) = rewrite_srcset(&srcset, |url| {
@@ -320,16 +851,165 @@ fn extract_asset_url(content: &str, anonymous: bool) -> ExtractAssetResult {
         Ok(())
     });
 
+    // Lightboxes link to original images, of which the images inside are downscaled previews.
+    let lightbox_rule = element!("a.lightbox[href]", |el| {
+        let url = el.get_attribute("href").expect("has href");
+        if filter_media(&url) {
+            el.set_attribute("href", &format!("resources/{}", url_to_filename(&url)))?;
+            urls.borrow_mut().push(url);
+        }
+        Ok(())
+    });
+
+    // Videos are lazily loaded by the site from placeholders.
+    let placeholder_rule = element!("[data-video-src], [data-thumbnail-src]", |el| {
+        rewrite_media_attr(el, "data-video-src", base_url, &urls, &untyped)?;
+        rewrite_media_attr(el, "data-thumbnail-src", base_url, &urls, &untyped)?;
+        Ok(())
+    });
+
+    let mut rules = vec![a_rule, img_rule, placeholder_rule];
+    if originals {
+        rules.push(lightbox_rule);
+    }
     let rewritten_content = rewrite_str(
         content,
         RewriteStrSettings {
-            element_content_handlers: vec![a_rule, img_rule],
+            element_content_handlers: rules,
             ..RewriteStrSettings::default()
         },
     )
     .unwrap();
     ExtractAssetResult {
         urls: urls.into_inner(),
+        untyped: untyped.into_inner(),
+        uploads: uploads.into_inner(),
         rewritten_content,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::archiver::options::AttachmentOptions;
+    use crate::models::{category_path, RespSiteCategory};
+    use crate::site::Site;
+
+    use super::{extract_asset_url, extract_attachment_urls, rewrite_typed_media, subcategories};
+
+    #[test]
+    fn must_detect_untyped_uploads() {
+        let content = r#"<img src="/uploads/default/original/3X/a/b/abc" data-base62-sha1="aBc"><audio controls><source src="/uploads/short-url/dEf.m4a"></audio><img src="https://example.com/banner"><div class="video-placeholder-container" data-video-src="/uploads/short-url/gHi"></div>"#;
+        let result = extract_asset_url(content, "https://shuiyuan.sjtu.edu.cn", false, true);
+        assert_eq!(result.urls, ["/uploads/short-url/dEf.m4a"]);
+        assert_eq!(
+            result.untyped,
+            [
+                "/uploads/default/original/3X/a/b/abc",
+                "/uploads/short-url/gHi"
+            ]
+        );
+
+        let typed = HashMap::from([
+            (
+                String::from("/uploads/default/original/3X/a/b/abc"),
+                String::from("resources/abc.webp"),
+            ),
+            (
+                String::from("/uploads/short-url/gHi"),
+                String::from("resources/gHi.webm"),
+            ),
+        ]);
+        let mut uploads = HashMap::new();
+        let content = rewrite_typed_media(
+            &result.rewritten_content,
+            &Site::default(),
+            &typed,
+            &mut uploads,
+        )
+        .unwrap();
+        assert_eq!(
+            content,
+            r#"<img src="resources/abc.webp" data-base62-sha1="aBc"><audio controls><source src="resources/dEf.m4a"></audio><img src="https://example.com/banner"><video controls preload="metadata"><source src="resources/gHi.webm" type="video/webm"></video>"#
+        );
+        assert_eq!(uploads["aBc"], "resources/abc.webp");
+    }
+
+    #[test]
+    fn must_archive_lightbox_originals() {
+        let content = r#"<a class="lightbox" href="/uploads/default/original/3X/a/b/abc.png"><img src="/uploads/default/optimized/3X/a/b/abc_2_690x388.png"></a>"#;
+
+        let result = extract_asset_url(content, "https://shuiyuan.sjtu.edu.cn", false, true);
+        assert_eq!(
+            result.urls,
+            [
+                "/uploads/default/original/3X/a/b/abc.png",
+                "/uploads/default/optimized/3X/a/b/abc_2_690x388.png"
+            ]
+        );
+        assert_eq!(
+            result.rewritten_content,
+            r#"<a class="lightbox" href="resources/abc.png"><img src="resources/abc_2_690x388.png"></a>"#
+        );
+
+        let result = extract_asset_url(content, "https://shuiyuan.sjtu.edu.cn", false, false);
+        assert_eq!(
+            result.urls,
+            ["/uploads/default/optimized/3X/a/b/abc_2_690x388.png"]
+        );
+    }
+
+    #[test]
+    fn must_extract_attachments() {
+        let content = r#"<p>
+<a class="attachment" href="/uploads/short-url/aBc.pdf">课件.pdf</a>
+<a class="attachment" href="https://shuiyuan.sjtu.edu.cn/uploads/short-url/dEf.zip">作业.zip</a>
+<a class="attachment" href="https://example.com/uploads/x.pdf">外部.pdf</a>
+<a class="attachment" href="/uploads/short-url/gHi.EXE">setup.exe</a>
+<a href="/uploads/short-url/jKl.pdf">普通链接</a>
+</p>"#;
+        let options = AttachmentOptions {
+            deny: vec![String::from("exe")],
+            ..AttachmentOptions::default()
+        };
+        assert_eq!(
+            extract_attachment_urls(content, "https://shuiyuan.sjtu.edu.cn", &options),
+            [
+                "/uploads/short-url/aBc.pdf",
+                "https://shuiyuan.sjtu.edu.cn/uploads/short-url/dEf.zip"
+            ]
+        );
+
+        let options = AttachmentOptions {
+            allow: vec![String::from(".PDF")],
+            ..AttachmentOptions::default()
+        };
+        assert_eq!(
+            extract_attachment_urls(content, "https://shuiyuan.sjtu.edu.cn", &options),
+            ["/uploads/short-url/aBc.pdf"]
+        );
+    }
+
+    #[test]
+    fn must_walk_subcategories() {
+        let category = |id, slug: &str, parent| RespSiteCategory {
+            id,
+            slug: slug.to_string(),
+            parent_category_id: parent,
+        };
+        let categories = [
+            category(1, "root", None),
+            category(2, "child", Some(1)),
+            category(3, "", Some(2)),
+            category(4, "other", None),
+            category(5, "sibling", Some(1)),
+        ];
+        let mut found = subcategories(1, &categories);
+        found.sort_unstable();
+        assert_eq!(found, [("", 3), ("child", 2), ("sibling", 5)]);
+        assert!(subcategories(4, &categories).is_empty());
+        assert_eq!(category_path("", 3), "/c/3");
+        assert_eq!(category_path("child", 2), "/c/child/2");
+    }
+}
@@ -0,0 +1,51 @@
+//! Raw JSON output.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::archiver::record::ArchivedPost;
+use crate::error::Result;
+
+pub const JSON_DIR: &str = "json";
+
+/// Write API responses of a topic as they are, so that the archive can be re-rendered later
+/// without being limited to what the archiver keeps.
+///
+/// Files written into `json/`:
+/// * `topic.json` - response of `/t/{id}.json`.
+/// * `posts_{n}.json` - responses of `/t/{id}/posts.json`, one per fetched chunk.
+/// * `cooked.json` - cooked contents keyed by post id, with asset urls rewritten to local files.
+///
+/// Original urls of downloaded assets are recorded in `archive.json`.
+pub fn write_json(
+    topic: &Value,
+    chunks: &[Value],
+    archived: &[ArchivedPost],
+    save_to: &Path,
+) -> Result<()> {
+    // Responses of last run may have more chunks.
+    let dir = save_to.join(JSON_DIR);
+    if dir.exists() {
+        fs::remove_dir_all(&dir)?;
+    }
+    fs::create_dir_all(&dir)?;
+
+    write_file(&dir.join("topic.json"), topic)?;
+    for (idx, chunk) in chunks.iter().enumerate() {
+        write_file(&dir.join(format!("posts_{}.json", idx + 1)), chunk)?;
+    }
+    let cooked: BTreeMap<u32, &str> = archived
+        .iter()
+        .map(|archived| (archived.id, archived.post.content.as_str()))
+        .collect();
+    write_file(&dir.join("cooked.json"), &cooked)
+}
+
+fn write_file(path: &Path, value: &impl Serialize) -> Result<()> {
+    fs::write(path, serde_json::to_vec_pretty(value)?)?;
+    Ok(())
+}
@@ -0,0 +1,254 @@
+//! Index of all archives under a root, however they were made.
+
+use std::fs;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::archiver::collection::url_escape;
+use crate::archiver::manifest::Manifest;
+use crate::archiver::template::Templates;
+use crate::archiver::utils;
+use crate::error::Result;
+
+/// Name of the library index page.
+pub const LIBRARY_INDEX: &str = "library.html";
+
+/// An archive found under the library root.
+#[derive(Debug, Clone, Serialize)]
+pub struct LibraryEntry {
+    pub id: u32,
+    pub title: String,
+    /// Base URL of the site the topic is archived from.
+    pub site: String,
+    /// Entry file of the archive relative to the library root.
+    pub link: String,
+    pub categories: Vec<String>,
+    pub tags: Vec<String>,
+    /// Unknown for archives without a manifest.
+    pub posts_count: Option<usize>,
+    pub private: bool,
+    pub updated_at: String,
+    /// `updated_at` as `YYYY-MM-DD`, for sorting and filtering.
+    pub updated_date: String,
+}
+
+/// Find all archives under given root, newest first.
+///
+/// An archive is a directory with a manifest, or an HTML archive without one, e.g. made by an
+/// older version. Subdirectories of an archive are not searched.
+#[must_use]
+pub fn scan_library(root: &Path) -> Vec<LibraryEntry> {
+    let mut entries = vec![];
+    scan_dir(root, root, &mut entries);
+    entries.sort_by(|a, b| b.updated_date.cmp(&a.updated_date));
+    entries
+}
+
+fn scan_dir(root: &Path, dir: &Path, entries: &mut Vec<LibraryEntry>) {
+    if let Some(entry) = archive_entry(root, dir) {
+        entries.push(entry);
+        return;
+    }
+    let Ok(children) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut children: Vec<_> = children
+        .filter_map(|child| child.ok().map(|child| child.path()))
+        .filter(|path| path.is_dir())
+        .collect();
+    children.sort();
+    for child in children {
+        scan_dir(root, &child, entries);
+    }
+}
+
+fn archive_entry(root: &Path, dir: &Path) -> Option<LibraryEntry> {
+    let relative = url_escape(&dir.strip_prefix(root).ok()?.to_string_lossy());
+    let link = |file: &str| {
+        if relative.is_empty() {
+            file.to_string()
+        } else {
+            format!("{relative}/{file}")
+        }
+    };
+
+    if let Ok(manifest) = Manifest::load(dir) {
+        return Some(LibraryEntry {
+            id: manifest.topic.id,
+            title: manifest.topic.title,
+            site: manifest.site,
+            link: link(manifest.format.entry_file()),
+            categories: manifest
+                .topic
+                .categories
+                .into_iter()
+                .map(|category| category.name)
+                .collect(),
+            tags: manifest.topic.tags,
+            posts_count: Some(manifest.posts.len()),
+            private: manifest.topic.private,
+            updated_at: utils::datetime_to_display(&manifest.archived_at),
+            updated_date: manifest.archived_at.format("%Y-%m-%d").to_string(),
+        });
+    }
+
+    let index = dir.join("index.html");
+    let html = fs::read_to_string(&index).ok()?;
+    let meta = TopicMetaTag::parse(&html)?;
+    let modified: DateTime<Utc> = fs::metadata(&index).ok()?.modified().ok()?.into();
+    Some(LibraryEntry {
+        id: meta.id,
+        title: meta.title,
+        site: meta.site,
+        link: link("index.html"),
+        categories: meta.categories,
+        tags: meta.tags,
+        posts_count: None,
+        private: meta.private,
+        updated_at: utils::datetime_to_display(&modified),
+        updated_date: modified.format("%Y-%m-%d").to_string(),
+    })
+}
+
+/// Topic metadata in the meta tag of an HTML archive page.
+#[derive(Debug, PartialEq, Eq)]
+struct TopicMetaTag {
+    id: u32,
+    title: String,
+    site: String,
+    categories: Vec<String>,
+    tags: Vec<String>,
+    private: bool,
+}
+
+impl TopicMetaTag {
+    fn parse(html: &str) -> Option<Self> {
+        static RE_META: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r#"<meta\s[^>]*data-topic-id="(\d+)"[^>]*>"#).unwrap());
+        static RE_ATTR: Lazy<Regex> =
+            Lazy::new(|| Regex::new(r#"data-([\w-]+)="([^"]*)""#).unwrap());
+
+        let caps = RE_META.captures(html)?;
+        let id = caps.get(1)?.as_str().parse().ok()?;
+        let attr = |name: &str| {
+            RE_ATTR
+                .captures_iter(caps.get(0).expect("whole match").as_str())
+                .find(|caps| &caps[1] == name)
+                .map(|caps| unescape(&caps[2]))
+        };
+        let list = |name: &str| {
+            attr(name)
+                .map(|value| {
+                    value
+                        .split(", ")
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                })
+                .unwrap_or_default()
+        };
+        Some(Self {
+            id,
+            title: attr("title").unwrap_or_default(),
+            site: attr("site").unwrap_or_default(),
+            categories: list("categories"),
+            tags: list("tags"),
+            private: attr("private").is_some_and(|value| value == "true"),
+        })
+    }
+}
+
+/// Reverse the escaping done by the `escape` template helper.
+fn unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&#x27;", "'")
+        .replace("&#x60;", "`")
+        .replace("&#x3D;", "=")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+/// Write the library index page into the root.
+///
+/// # Errors
+///
+/// Returns error if failed to render or write the page.
+pub fn write_library_index(
+    entries: &[LibraryEntry],
+    root: &Path,
+    templates: &Templates,
+) -> Result<()> {
+    #[derive(Serialize)]
+    struct Params<'a> {
+        entries: &'a [LibraryEntry],
+        categories: Vec<&'a str>,
+        tags: Vec<&'a str>,
+        total: usize,
+        generated_at: String,
+        app_version: &'static str,
+    }
+
+    let mut categories: Vec<&str> = entries
+        .iter()
+        .flat_map(|entry| entry.categories.iter().map(String::as_str))
+        .collect();
+    categories.sort_unstable();
+    categories.dedup();
+    let mut tags: Vec<&str> = entries
+        .iter()
+        .flat_map(|entry| entry.tags.iter().map(String::as_str))
+        .collect();
+    tags.sort_unstable();
+    tags.dedup();
+
+    let output = templates.render(
+        "library",
+        &Params {
+            entries,
+            categories,
+            tags,
+            total: entries.len(),
+            generated_at: utils::datetime_to_display(&Utc::now()),
+            app_version: env!("CARGO_PKG_VERSION"),
+        },
+    )?;
+    fs::write(root.join(LIBRARY_INDEX), output)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TopicMetaTag;
+
+    #[test]
+    fn must_parse_meta_tag() {
+        let html = r#"<head>
+    <meta
+            data-topic-id="42"
+            data-title="Tom &amp; Jerry&#x27;s"
+            data-page="1"
+            data-total-pages="3"
+            data-categories="校园, 生活"
+            data-tags=""
+            data-archiver-name="shuiyuan-archiver"
+            data-site="https://shuiyuan.sjtu.edu.cn"
+    />"#;
+        assert_eq!(
+            TopicMetaTag::parse(html),
+            Some(TopicMetaTag {
+                id: 42,
+                title: String::from("Tom & Jerry's"),
+                site: String::from("https://shuiyuan.sjtu.edu.cn"),
+                categories: vec![String::from("校园"), String::from("生活")],
+                tags: vec![],
+                private: false,
+            })
+        );
+        assert_eq!(TopicMetaTag::parse("<meta data-username=\"foo\">"), None);
+    }
+}
@@ -0,0 +1,176 @@
+//! Machine-readable manifest written next to the rendered archive.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use tracing::warn;
+
+use crate::archiver::options::{ArchiveFormat, ArchiveOptions, Theme};
+use crate::archiver::record::ArchivedPost;
+use crate::error::Result;
+use crate::models::{Post, TopicMeta};
+use crate::site::Site;
+
+pub const MANIFEST_FILE: &str = "archive.json";
+pub const MANIFEST_VERSION: u32 = 1;
+
+/// Manifest of an archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct Manifest {
+    /// Version of the manifest format.
+    pub version: u32,
+    pub archiver_name: String,
+    pub archiver_version: String,
+    pub archived_at: DateTime<Utc>,
+    /// Base URL of the site the topic is archived from.
+    pub site: String,
+    pub topic: TopicMeta,
+    pub anonymous: bool,
+    /// Output format of the archive.
+    #[serde(default)]
+    pub format: ArchiveFormat,
+    /// Color theme the HTML pages open with.
+    #[serde(default)]
+    pub theme: Theme,
+    pub posts: Vec<PostManifest>,
+    /// Downloaded assets, keyed by their original URLs.
+    pub assets: BTreeMap<String, AssetManifest>,
+}
+
+/// Metadata of an archived post.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct PostManifest {
+    pub id: u32,
+    pub number: usize,
+    pub username: String,
+    pub name: String,
+    pub created_at: String,
+    pub updated_at: DateTime<Utc>,
+    pub reply_to: Option<usize>,
+    pub likes: usize,
+    /// Exported page the post is on, starting from 1.
+    pub page: usize,
+}
+
+/// A downloaded asset.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AssetManifest {
+    /// Path of the asset relative to the archive root.
+    pub filename: PathBuf,
+    pub size: u64,
+    pub sha256: String,
+}
+
+impl Manifest {
+    /// Load the manifest in given directory.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if the manifest can't be read or parsed.
+    pub fn load(dir: &Path) -> Result<Self> {
+        let data = fs::read(dir.join(MANIFEST_FILE))?;
+        Ok(serde_json::from_slice(&data)?)
+    }
+
+    /// Build the manifest of current run.
+    ///
+    /// Assets of a previous manifest are kept if they still exist, because posts which are not
+    /// changed since last run don't download their assets again. Single HTML archives download
+    /// all their assets every run, so previous ones are dropped.
+    #[allow(clippy::too_many_arguments)]
+    pub fn build(
+        save_to: &Path,
+        site: &Site,
+        meta: &TopicMeta,
+        options: &ArchiveOptions,
+        archived: &[ArchivedPost],
+        posts: &[Post],
+        pages: &[Vec<u32>],
+        downloaded: HashMap<String, PathBuf>,
+    ) -> Self {
+        let page_of: HashMap<u32, usize> = pages
+            .iter()
+            .enumerate()
+            .flat_map(|(page, ids)| ids.iter().map(move |id| (*id, page + 1)))
+            .collect();
+        let posts = archived
+            .iter()
+            .zip(posts)
+            .map(|(archived, post)| PostManifest {
+                id: archived.id,
+                number: post.number,
+                username: post.username.clone(),
+                name: post.name.clone(),
+                created_at: post.created_at.clone(),
+                updated_at: archived.updated_at,
+                reply_to: post.reply_to,
+                likes: post.likes,
+                page: page_of.get(&archived.id).copied().unwrap_or_default(),
+            })
+            .collect();
+
+        let previous = Self::load(save_to)
+            .ok()
+            .filter(|manifest| manifest.site == site.base_url && manifest.topic.id == meta.id)
+            .filter(|_| options.format != ArchiveFormat::SingleHtml)
+            .map(|manifest| manifest.assets)
+            .unwrap_or_default();
+        let mut assets = BTreeMap::new();
+        for (url, filename) in previous
+            .into_iter()
+            .map(|(url, asset)| (url, asset.filename))
+            .chain(downloaded)
+        {
+            match hash_file(&save_to.join(&filename)) {
+                Ok((size, sha256)) => {
+                    assets.insert(
+                        url,
+                        AssetManifest {
+                            filename,
+                            size,
+                            sha256,
+                        },
+                    );
+                }
+                Err(e) => warn!(?filename, ?e, "Asset missing when building manifest"),
+            }
+        }
+
+        Self {
+            version: MANIFEST_VERSION,
+            archiver_name: String::from("shuiyuan-archiver"),
+            archiver_version: env!("CARGO_PKG_VERSION").to_string(),
+            archived_at: Utc::now(),
+            site: site.base_url.clone(),
+            topic: meta.clone(),
+            anonymous: options.anonymous,
+            format: options.format,
+            theme: options.theme,
+            posts,
+            assets,
+        }
+    }
+
+    pub fn save(&self, save_to: &Path) -> Result<()> {
+        fs::write(
+            save_to.join(MANIFEST_FILE),
+            serde_json::to_vec_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+fn hash_file(path: &Path) -> io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let size = io::copy(&mut File::open(path)?, &mut hasher)?;
+    Ok((size, format!("{:x}", hasher.finalize())))
+}
@@ -0,0 +1,204 @@
+//! Markdown output.
+
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+
+use lol_html::html_content::{ContentType, Element};
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+
+use crate::error::Result;
+use crate::models::{Post, TopicMeta};
+use crate::site::Site;
+
+pub const MARKDOWN_FILE: &str = "index.md";
+
+/// Write all posts of a topic into a single Markdown file.
+pub fn write_markdown(site: &Site, meta: &TopicMeta, posts: &[Post], save_to: &Path) -> Result<()> {
+    let mut output = format!("# {}\n\n", meta.title);
+    writeln!(output, "- 原帖：{}/t/topic/{}", site.base_url, meta.id).unwrap();
+    if !meta.categories.is_empty() {
+        let categories: Vec<_> = meta.categories.iter().map(|c| c.name.as_str()).collect();
+        writeln!(output, "- 分类：{}", categories.join(" / ")).unwrap();
+    }
+    if !meta.tags.is_empty() {
+        writeln!(output, "- 标签：{}", meta.tags.join(", ")).unwrap();
+    }
+    writeln!(
+        output,
+        "- 由 shuiyuan-archiver {} 存档",
+        env!("CARGO_PKG_VERSION")
+    )
+    .unwrap();
+
+    for post in posts {
+        output.push_str("\n---\n\n");
+        write_post(&mut output, post);
+    }
+
+    fs::write(save_to.join(MARKDOWN_FILE), output)?;
+    Ok(())
+}
+
+fn write_post(output: &mut String, post: &Post) {
+    writeln!(output, "<a id=\"post_{}\"></a>\n", post.number).unwrap();
+    write!(output, "### #{} {}", post.number, post.username).unwrap();
+    if !post.name.is_empty() {
+        write!(output, " ({})", post.name).unwrap();
+    }
+    writeln!(output, " · {}\n", post.created_at_display).unwrap();
+    if let Some(reply_to) = post.reply_to {
+        writeln!(output, "> 回复 [#{reply_to}](#post_{reply_to})\n").unwrap();
+    }
+
+    let body = post
+        .raw
+        .clone()
+        .unwrap_or_else(|| html_to_markdown(&post.content));
+    output.push_str(body.trim());
+    output.push('\n');
+
+    if post.likes > 0 {
+        writeln!(output, "\n{} 个赞", post.likes).unwrap();
+    }
+}
+
+/// Point `upload://` short URLs in raw post sources to local files, or to the site if they are
+/// not downloaded.
+pub fn rewrite_upload_links(raw: &str, uploads: &HashMap<String, String>, site: &Site) -> String {
+    static RE_UPLOAD: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r"upload://([0-9A-Za-z]+)(\.[0-9A-Za-z]+)?").unwrap());
+    RE_UPLOAD
+        .replace_all(raw, |caps: &Captures| {
+            let sha1 = &caps[1];
+            uploads.get(sha1).cloned().unwrap_or_else(|| {
+                let ext = caps.get(2).map_or("", |ext| ext.as_str());
+                site.url(&format!("/uploads/short-url/{sha1}{ext}"))
+            })
+        })
+        .to_string()
+}
+
+/// Convert cooked HTML into Markdown.
+///
+/// This is a best-effort conversion used when raw sources are unavailable, e.g. for system
+/// messages, hidden posts and anonymized posts.
+pub fn html_to_markdown(html: &str) -> String {
+    static RE_BLANK_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n\s*\n(\s*\n)+").unwrap());
+
+    let rule = element!("*", |el| {
+        let tag = el.tag_name();
+        match tag.as_str() {
+            "script" | "style" => {
+                el.remove();
+                return Ok(());
+            }
+            "img" => {
+                let alt = el.get_attribute("alt").unwrap_or_default();
+                let is_emoji = el
+                    .get_attribute("class")
+                    .is_some_and(|class| class.contains("emoji"));
+                let replacement = if is_emoji {
+                    alt
+                } else {
+                    let src = el.get_attribute("src").unwrap_or_default();
+                    format!("![{alt}]({src})")
+                };
+                el.replace(&replacement, ContentType::Html);
+                return Ok(());
+            }
+            "br" => el.before("  \n", ContentType::Html),
+            "hr" => el.before("\n---\n\n", ContentType::Html),
+            "p" | "div" | "aside" | "table" | "ul" | "ol" => el.after("\n\n", ContentType::Html),
+            "tr" => el.after("\n", ContentType::Html),
+            "td" | "th" => el.after(" ", ContentType::Html),
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level: usize = tag[1..].parse().expect("heading level");
+                wrap(el, &format!("\n{} ", "#".repeat(level)), "\n\n");
+            }
+            "strong" | "b" => wrap(el, "**", "**"),
+            "em" | "i" => wrap(el, "*", "*"),
+            "del" | "s" | "strike" => wrap(el, "~~", "~~"),
+            // Code blocks are cooked as `<pre><code class="lang-xxx">`.
+            "code" if el.get_attribute("class").is_none() => wrap(el, "`", "`"),
+            "pre" => wrap(el, "\n```\n", "\n```\n\n"),
+            "blockquote" => wrap(el, "\n> ", "\n\n"),
+            "li" => wrap(el, "- ", "\n"),
+            "a" => {
+                if let Some(href) = el.get_attribute("href") {
+                    wrap(el, "[", &format!("]({href})"));
+                }
+            }
+            _ => {}
+        }
+        el.remove_and_keep_content();
+        Ok(())
+    });
+
+    let markdown = rewrite_str(
+        html,
+        RewriteStrSettings {
+            element_content_handlers: vec![rule],
+            ..RewriteStrSettings::default()
+        },
+    )
+    .unwrap_or_else(|_| html.to_string());
+    let markdown = htmlescape::decode_html(&markdown).unwrap_or(markdown);
+    RE_BLANK_LINES
+        .replace_all(markdown.trim(), "\n\n")
+        .to_string()
+}
+
+fn wrap(el: &mut Element, before: &str, after: &str) {
+    el.before(before, ContentType::Html);
+    el.after(after, ContentType::Html);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use crate::site::Site;
+
+    use super::{html_to_markdown, rewrite_upload_links};
+
+    #[test]
+    fn must_convert_html() {
+        assert_eq!(
+            html_to_markdown(
+                r#"<p>Hello <strong>world</strong> &amp; <a href="/t/1">link</a></p>"#
+            ),
+            "Hello **world** & [link](/t/1)"
+        );
+        assert_eq!(
+            html_to_markdown(
+                r#"<p><img src="resources/a.png" alt="pic"><img src="e.png" class="emoji" alt=":smile:"></p>"#
+            ),
+            "![pic](resources/a.png):smile:"
+        );
+        assert_eq!(
+            html_to_markdown("<ul><li>a</li><li>b</li></ul><p>c</p>"),
+            "- a\n- b\n\nc"
+        );
+    }
+
+    #[test]
+    fn must_rewrite_upload_links() {
+        let uploads = HashMap::from([(String::from("abc"), String::from("resources/abc.png"))]);
+        let site = Site::default();
+        assert_eq!(
+            rewrite_upload_links(
+                "![a|690x388](upload://abc.png) [b](upload://def.pdf)",
+                &uploads,
+                &site
+            ),
+            format!(
+                "![a|690x388](resources/abc.png) [b]({}/uploads/short-url/def.pdf)",
+                site.base_url
+            )
+        );
+    }
+}
@@ -0,0 +1,166 @@
+use std::path::Path;
+
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+
+use crate::archiver::epub::EPUB_FILE;
+use crate::archiver::manifest::MANIFEST_FILE;
+use crate::archiver::markdown::MARKDOWN_FILE;
+use crate::archiver::single_html::SINGLE_HTML_FILE;
+
+/// Output format of an archive.
+#[typeshare]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ArchiveFormat {
+    /// Paginated HTML pages rendered from the bundled template.
+    #[default]
+    Html,
+    /// A single Markdown file built from raw post sources.
+    Markdown,
+    /// Unmodified API responses, along with post contents whose asset urls are rewritten.
+    Json,
+    /// A single HTML file with all resources inlined.
+    SingleHtml,
+    /// An EPUB 3 book with a chapter per page.
+    Epub,
+}
+
+impl ArchiveFormat {
+    /// The file to open an archive of this format with.
+    #[must_use]
+    pub const fn entry_file(self) -> &'static str {
+        match self {
+            Self::Html => "index.html",
+            Self::Markdown => MARKDOWN_FILE,
+            Self::Json => MANIFEST_FILE,
+            Self::SingleHtml => SINGLE_HTML_FILE,
+            Self::Epub => EPUB_FILE,
+        }
+    }
+}
+
+/// Posts on a page of paginated output by default.
+pub const DEFAULT_PAGE_SIZE: u32 = 20;
+
+/// How posts are split into pages of paginated output.
+#[typeshare]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "mode", content = "value", rename_all = "kebab-case")]
+pub enum Pagination {
+    /// At most given number of posts on a page.
+    Posts(u32),
+    /// All posts on one page.
+    Single,
+    /// Pages of at most given size in MiB, counting post contents and the local resources they
+    /// use. A post larger than that gets a page of its own.
+    Size(u32),
+}
+
+impl Default for Pagination {
+    fn default() -> Self {
+        Self::Posts(DEFAULT_PAGE_SIZE)
+    }
+}
+
+/// Color theme of HTML output. Readers can switch themes in the pages.
+#[typeshare]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Theme {
+    #[default]
+    Light,
+    Dark,
+    /// Follow the color scheme of the system.
+    Auto,
+}
+
+/// Which attachments, i.e. uploaded files other than images and videos, are downloaded.
+/// Attachments not downloaded are linked to the site.
+#[typeshare]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct AttachmentOptions {
+    /// Whether to download attachments.
+    pub download: bool,
+    /// Attachments larger than this (in MiB) are not downloaded. No limit if not set.
+    pub size_limit_mib: Option<u32>,
+    /// Extensions of attachments to download, case-insensitive. All extensions if empty.
+    #[serde(default)]
+    pub allow: Vec<String>,
+    /// Extensions of attachments never to download, case-insensitive.
+    #[serde(default)]
+    pub deny: Vec<String>,
+}
+
+impl Default for AttachmentOptions {
+    fn default() -> Self {
+        Self {
+            download: true,
+            size_limit_mib: None,
+            allow: vec![],
+            deny: vec![],
+        }
+    }
+}
+
+impl AttachmentOptions {
+    /// Whether attachments of given extension are downloaded, regardless of their sizes.
+    #[must_use]
+    pub fn accepts(&self, ext: &str) -> bool {
+        let ext = ext.trim_start_matches('.');
+        let listed = |list: &[String]| {
+            list.iter()
+                .any(|item| item.trim_start_matches('.').eq_ignore_ascii_case(ext))
+        };
+        self.download && (self.allow.is_empty() || listed(&self.allow)) && !listed(&self.deny)
+    }
+
+    /// Size limit in bytes.
+    #[must_use]
+    pub fn size_limit(&self) -> Option<u64> {
+        self.size_limit_mib.map(|mib| u64::from(mib) * 1024 * 1024)
+    }
+}
+
+/// Options of an archive.
+#[typeshare]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ArchiveOptions {
+    /// Whether to anonymize usernames.
+    pub anonymous: bool,
+    pub format: ArchiveFormat,
+    /// Resources larger than this (in MiB) are linked to the site instead of being inlined in
+    /// single-file output. No limit if not set.
+    pub inline_limit_mib: Option<u32>,
+    /// How posts are split into pages. Only applies to HTML and EPUB output.
+    #[serde(default)]
+    pub pagination: Pagination,
+    /// Whether to show posts along their reply trees. Only applies to HTML output.
+    #[serde(default)]
+    pub threaded: bool,
+    /// Directory of templates, helpers and resources overriding the embedded ones.
+    #[serde(default)]
+    pub template_dir: Option<String>,
+    /// Color theme the pages open with. Only applies to HTML output.
+    #[serde(default)]
+    pub theme: Theme,
+    /// Whether to use stylesheets of the live site instead of the embedded ones, which may be
+    /// outdated. Only applies to HTML output.
+    #[serde(default)]
+    pub live_styles: bool,
+    #[serde(default)]
+    pub attachments: AttachmentOptions,
+    /// Whether to archive only downscaled previews of images, instead of also the originals
+    /// they open in full size.
+    #[serde(default)]
+    pub previews_only: bool,
+}
+
+impl ArchiveOptions {
+    #[must_use]
+    pub fn template_dir(&self) -> Option<&Path> {
+        self.template_dir.as_deref().map(Path::new)
+    }
+}
@@ -0,0 +1,129 @@
+//! Split posts into pages.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+use crate::archiver::options::Pagination;
+use crate::models::Post;
+
+/// Split posts into pages, returning the range of posts on each page.
+///
+/// Resources are looked up in `resources_dir` to measure pages in size-aware mode. There's always
+/// at least one page, even if there are no posts.
+pub fn paginate(posts: &[Post], pagination: Pagination, resources_dir: &Path) -> Vec<Range<usize>> {
+    let ranges = match pagination {
+        Pagination::Posts(size) => {
+            let size = (size as usize).max(1);
+            (0..posts.len())
+                .step_by(size)
+                .map(|start| start..(start + size).min(posts.len()))
+                .collect()
+        }
+        Pagination::Single => vec![0..posts.len()],
+        Pagination::Size(mib) => {
+            let mut sizes = HashMap::new();
+            split_by_size(posts, u64::from(mib) * 1024 * 1024, |filename| {
+                *sizes.entry(filename.to_string()).or_insert_with(|| {
+                    fs::metadata(resources_dir.join(filename)).map_or(0, |meta| meta.len())
+                })
+            })
+        }
+    };
+    if ranges.is_empty() {
+        vec![0..0]
+    } else {
+        ranges
+    }
+}
+
+/// Greedily fill pages up to `limit` bytes. A resource used by several posts on a page is counted
+/// once.
+fn split_by_size(
+    posts: &[Post],
+    limit: u64,
+    mut resource_size: impl FnMut(&str) -> u64,
+) -> Vec<Range<usize>> {
+    static RE_RESOURCE: Lazy<Regex> =
+        Lazy::new(|| Regex::new(r#"resources/([^"'\s)?#]+)"#).unwrap());
+
+    let mut ranges = vec![];
+    let mut start = 0;
+    let mut page_size = 0;
+    let mut page_resources: HashSet<&str> = HashSet::new();
+    for (idx, post) in posts.iter().enumerate() {
+        let resources: HashSet<&str> = RE_RESOURCE
+            .captures_iter(&post.content)
+            .map(|caps| caps.get(1).expect("has group").as_str())
+            .collect();
+        let mut size_on = |page_resources: &HashSet<&str>| {
+            post.content.len() as u64
+                + resources
+                    .iter()
+                    .filter(|r| !page_resources.contains(*r))
+                    .map(|r| resource_size(r))
+                    .sum::<u64>()
+        };
+
+        let size = size_on(&page_resources);
+        if idx > start && page_size + size > limit {
+            ranges.push(start..idx);
+            start = idx;
+            page_resources.clear();
+            page_size = size_on(&page_resources);
+        } else {
+            page_size += size;
+        }
+        page_resources.extend(resources);
+    }
+    if start < posts.len() {
+        ranges.push(start..posts.len());
+    }
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use crate::archiver::options::Pagination;
+    use crate::models::Post;
+
+    use super::{paginate, split_by_size};
+
+    #[test]
+    fn must_paginate_by_count() {
+        let posts = vec![Post::default(); 5];
+        let dir = Path::new("");
+        assert_eq!(
+            paginate(&posts, Pagination::Posts(2), dir),
+            [0..2, 2..4, 4..5]
+        );
+        assert_eq!(paginate(&posts, Pagination::Single, dir), [0..5]);
+        assert_eq!(paginate(&[], Pagination::Posts(2), dir), [0..0]);
+    }
+
+    #[test]
+    fn must_paginate_by_size() {
+        let posts = [
+            r#"<img src="resources/a.png">"#, // 27 + 100
+            r#"<img src="resources/a.png">"#, // 27, a.png is on the page
+            r#"<img src="resources/b.png">"#, // 27 + 500, too large for any page
+            "0123456789",                     // 10
+        ]
+        .map(|content| Post {
+            content: content.to_string(),
+            ..Post::default()
+        });
+        let size = |filename: &str| match filename {
+            "a.png" => 100,
+            "b.png" => 500,
+            _ => 0,
+        };
+        assert_eq!(split_by_size(&posts, 200, size), [0..2, 2..3, 3..4]);
+    }
+}
@@ -0,0 +1,272 @@
+//! A single page of selected posts grouped by topic, e.g. posts of a user or search results.
+
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+use futures::stream::FuturesOrdered;
+use futures::TryStreamExt;
+use serde::Serialize;
+use tokio::sync::mpsc::Sender;
+
+use crate::archiver::collection::{find_archives, url_escape};
+use crate::archiver::download_manager::DownloadManager;
+use crate::archiver::manifest::Manifest;
+use crate::archiver::options::{ArchiveFormat, ArchiveOptions, Theme};
+use crate::archiver::{
+    page_filename, process_resp_post, stylesheets, template, DownloadEvent, FETCH_PAGE_SIZE,
+};
+use crate::client::{Client, RequestBuilderExt, MAX_CONN, MAX_THROTTLE_WEIGHT};
+use crate::error::{Error, Result};
+use crate::models::{Post, RespPost, RespPosts};
+use crate::preloaded_store::{PreloadedStore, Stylesheet};
+use crate::site::Site;
+
+/// Selected posts of a topic.
+pub struct TopicPosts {
+    pub id: u32,
+    pub title: String,
+    /// Shown next to the topic title.
+    pub note: Option<String>,
+    /// Posts to show, in the order of the topic.
+    pub post_ids: Vec<u32>,
+    /// Posts to highlight among them, e.g. search hits shown with their context.
+    pub highlighted: HashSet<u32>,
+}
+
+/// A page of selected posts.
+pub struct PostList {
+    pub heading: String,
+    pub summary: String,
+    /// Where the posts are listed on the site.
+    pub source: String,
+    pub filename: String,
+    pub topics: Vec<TopicPosts>,
+}
+
+#[derive(Serialize)]
+struct TopicParams {
+    id: u32,
+    title: String,
+    url: String,
+    note: Option<String>,
+    /// Link to the full archive of the topic, if any.
+    archive: Option<String>,
+    posts: Vec<PostParams>,
+}
+
+#[derive(Serialize)]
+struct PostParams {
+    #[serde(flatten)]
+    post: Post,
+    highlighted: bool,
+    /// Link to the post in the full archive of the topic, if any.
+    archive: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Params<'a> {
+    heading: &'a str,
+    summary: &'a str,
+    source: &'a str,
+    topics: Vec<TopicParams>,
+    site: &'a Site,
+    theme: Theme,
+    stylesheets: &'a [Stylesheet],
+    app_version: &'static str,
+}
+
+/// Archive selected posts into a single page.
+///
+/// If a topic is archived in a subdirectory of `save_to`, its posts are linked to the full
+/// archive. Post lists can't be anonymized and are only available in HTML.
+pub async fn archive_post_list(
+    client: &Client,
+    list: PostList,
+    save_to: &Path,
+    options: &ArchiveOptions,
+    reporter: Sender<DownloadEvent>,
+) -> Result<()> {
+    if options.anonymous {
+        return Err(Error::UnsupportedOptions("post lists can't be anonymized"));
+    }
+    if options.format != ArchiveFormat::Html {
+        return Err(Error::UnsupportedOptions(
+            "post lists are only available in HTML",
+        ));
+    }
+
+    let handlebars = template::handlebars(options.template_dir())?;
+    let preloaded_store = PreloadedStore::from_client(client).await?;
+    fs::create_dir_all(save_to.join("resources"))?;
+    template::extract_resources(save_to.join("resources"), options.template_dir())?;
+    let download_manager =
+        DownloadManager::new(client.clone(), save_to.to_path_buf(), reporter.clone());
+
+    // Fetch all posts first, then download their assets.
+    reporter
+        .send(DownloadEvent::PostChunksTotal(list.topics.len() as u32))
+        .await?;
+    let futs: FuturesOrdered<_> = list
+        .topics
+        .iter()
+        .map(|topic| {
+            let reporter = reporter.clone();
+            async move {
+                let posts = fetch_posts(client, topic).await?;
+                reporter
+                    .send(DownloadEvent::PostChunksDownloadedInc)
+                    .await?;
+                Ok::<_, Error>(posts)
+            }
+        })
+        .collect();
+    let resp_posts: Vec<Vec<RespPost>> = futs.try_collect().await?;
+
+    let archives = find_archives(save_to);
+    let mut topics = Vec::with_capacity(list.topics.len());
+    for (topic, resp_posts) in list.topics.into_iter().zip(resp_posts) {
+        let futs: FuturesOrdered<_> = resp_posts
+            .into_iter()
+            .map(|resp_post| async {
+                let highlighted = topic.highlighted.contains(&(resp_post.id as u32));
+                let post = process_resp_post(
+                    client,
+                    &download_manager,
+                    &preloaded_store,
+                    options,
+                    resp_post,
+                )
+                .await?;
+                Ok::<_, Error>((post, highlighted))
+            })
+            .collect();
+        let posts: Vec<(Post, bool)> = futs.try_collect().await?;
+
+        let archive = archives.get(&topic.id).and_then(|dir| {
+            let manifest = Manifest::load(dir).ok()?;
+            let dir = url_escape(&dir.strip_prefix(save_to).ok()?.to_string_lossy());
+            Some((dir, manifest))
+        });
+        topics.push(TopicParams {
+            url: client.site().url(&format!("/t/topic/{}", topic.id)),
+            archive: archive
+                .as_ref()
+                .map(|(dir, manifest)| format!("{dir}/{}", manifest.format.entry_file())),
+            posts: posts
+                .into_iter()
+                .map(|(post, highlighted)| PostParams {
+                    archive: archive
+                        .as_ref()
+                        .map(|(dir, manifest)| post_link(dir, manifest, post.number)),
+                    highlighted,
+                    post,
+                })
+                .collect(),
+            id: topic.id,
+            title: topic.title,
+            note: topic.note,
+        });
+    }
+    let stylesheets = if options.live_styles {
+        stylesheets::download_stylesheets(&download_manager, &preloaded_store).await
+    } else {
+        vec![]
+    };
+
+    let params = Params {
+        heading: &list.heading,
+        summary: &list.summary,
+        source: &list.source,
+        topics,
+        site: client.site(),
+        theme: options.theme,
+        stylesheets: &stylesheets,
+        app_version: env!("CARGO_PKG_VERSION"),
+    };
+    let output = handlebars.render("post_list", &params)?;
+    fs::write(save_to.join(&list.filename), output)?;
+    Ok(())
+}
+
+async fn fetch_posts(client: &Client, topic: &TopicPosts) -> Result<Vec<RespPost>> {
+    let mut posts = vec![];
+    for post_ids in topic.post_ids.chunks(FETCH_PAGE_SIZE) {
+        let url = client.site().url(&format!("/t/{}/posts.json", topic.id));
+        let query: Vec<_> = post_ids.iter().map(|i| ("post_ids[]", i)).collect();
+        let req = client
+            .get(url)
+            .query(&query)
+            .with_conn_weight(MAX_CONN as u32)
+            .with_throttle_weight(MAX_THROTTLE_WEIGHT);
+        let resp: RespPosts = client.send_json(req).await?;
+        posts.extend(resp.post_stream.posts);
+    }
+    Ok(posts)
+}
+
+/// Link to a post in the full archive of its topic.
+fn post_link(dir: &str, manifest: &Manifest, number: usize) -> String {
+    let page = manifest
+        .posts
+        .iter()
+        .find(|post| post.number == number)
+        .map(|post| post.page);
+    match (manifest.format, page) {
+        (ArchiveFormat::Html, Some(page)) => {
+            format!("{dir}/{}#post_{number}", page_filename(page))
+        }
+        (ArchiveFormat::SingleHtml, Some(_)) => {
+            format!("{dir}/{}#post_{number}", manifest.format.entry_file())
+        }
+        (format, _) => format!("{dir}/{}", format.entry_file()),
+    }
+}
+
+/// Posts within `context` posts of any hit, in the order of the topic.
+///
+/// Hits missing from the stream, e.g. if the stream is not fetched, are kept on their own.
+pub(super) fn context_window(stream: &[u32], hits: &HashSet<u32>, context: usize) -> Vec<u32> {
+    let mut included = vec![false; stream.len()];
+    for (idx, _) in stream
+        .iter()
+        .enumerate()
+        .filter(|(_, id)| hits.contains(id))
+    {
+        let end = (idx + context + 1).min(stream.len());
+        included[idx.saturating_sub(context)..end].fill(true);
+    }
+    let mut post_ids: Vec<u32> = stream
+        .iter()
+        .zip(included)
+        .filter_map(|(id, included)| included.then_some(*id))
+        .collect();
+    let mut missing: Vec<u32> = hits
+        .iter()
+        .filter(|id| !stream.contains(id))
+        .copied()
+        .collect();
+    missing.sort_unstable();
+    post_ids.extend(missing);
+    post_ids
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use super::context_window;
+
+    #[test]
+    fn must_window_context() {
+        let stream = [1, 2, 3, 4, 5, 6, 7, 8, 9, 10];
+        let hits = HashSet::from([2, 9]);
+        assert_eq!(context_window(&stream, &hits, 1), vec![1, 2, 3, 8, 9, 10]);
+        assert_eq!(context_window(&stream, &hits, 0), vec![2, 9]);
+        assert_eq!(
+            context_window(&stream, &hits, 3),
+            vec![1, 2, 3, 4, 5, 6, 7, 8, 9, 10]
+        );
+        assert_eq!(context_window(&[], &hits, 2), vec![2, 9]);
+    }
+}
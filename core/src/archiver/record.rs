@@ -0,0 +1,198 @@
+//! Record of archived posts, used to update an archive incrementally.
+
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::Write;
+use std::path::Path;
+
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tempfile::NamedTempFile;
+use tracing::warn;
+
+use crate::archiver::options::{ArchiveFormat, ArchiveOptions};
+use crate::archiver::utils;
+use crate::error::Result;
+use crate::models::{Post, TopicMeta};
+use crate::site::Site;
+
+const RECORD_FILE: &str = ".archive-record.json";
+const RECORD_VERSION: u32 = 1;
+
+/// A post as it was rendered into the archive.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivedPost {
+    pub id: u32,
+    pub updated_at: DateTime<Utc>,
+    pub post: Post,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct ArchiveRecord {
+    version: u32,
+    app_version: String,
+    site: String,
+    topic_id: u32,
+    format: ArchiveFormat,
+    /// Everything besides posts which affects rendered pages, e.g. the theme and stylesheets.
+    #[serde(default)]
+    layout: String,
+    /// Hash of topic meta shown on every page, e.g. the title, categories and tags.
+    #[serde(default)]
+    meta: u64,
+    /// Options which affect how assets of posts are archived, e.g. attachment filters and whether
+    /// to archive originals of images. Posts archived with other options are processed again.
+    #[serde(default)]
+    assets: String,
+    /// Post ids of each exported page.
+    pages: Vec<Vec<u32>>,
+    posts: Vec<ArchivedPost>,
+    #[serde(skip)]
+    index: HashMap<u32, usize>,
+}
+
+impl ArchiveRecord {
+    /// Load the record of a previous run from given directory.
+    ///
+    /// An empty record is returned if there's no usable record, e.g. it's of another topic or
+    /// written by another version of archiver.
+    pub fn load(save_to: &Path, site: &Site, meta: &TopicMeta, options: &ArchiveOptions) -> Self {
+        let path = save_to.join(RECORD_FILE);
+        let Ok(data) = fs::read(&path) else {
+            return Self::default();
+        };
+        let mut record: Self = match serde_json::from_slice(&data) {
+            Ok(record) => record,
+            Err(e) => {
+                warn!(?path, ?e, "Failed to parse archive record, ignoring");
+                return Self::default();
+            }
+        };
+        if record.version != RECORD_VERSION
+            || record.app_version != env!("CARGO_PKG_VERSION")
+            || record.site != site.base_url
+            || record.topic_id != meta.id
+            || record.format != options.format
+            || record.assets != assets_key(options)
+        {
+            return Self::default();
+        }
+        record.index = record
+            .posts
+            .iter()
+            .enumerate()
+            .map(|(idx, post)| (post.id, idx))
+            .collect();
+        record
+    }
+
+    /// Create a record of current run.
+    pub fn new(
+        site: &Site,
+        meta: &TopicMeta,
+        options: &ArchiveOptions,
+        layout: String,
+        pages: Vec<Vec<u32>>,
+        posts: Vec<ArchivedPost>,
+    ) -> Self {
+        Self {
+            version: RECORD_VERSION,
+            app_version: env!("CARGO_PKG_VERSION").to_string(),
+            site: site.base_url.clone(),
+            topic_id: meta.id,
+            format: options.format,
+            layout,
+            meta: meta_hash(meta),
+            assets: assets_key(options),
+            pages,
+            posts,
+            index: HashMap::new(),
+        }
+    }
+
+    /// Remove the record in given directory, if any.
+    pub fn remove(save_to: &Path) -> Result<()> {
+        let path = save_to.join(RECORD_FILE);
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+        Ok(())
+    }
+
+    /// Save the record into given directory.
+    ///
+    /// It's written to a temporary file first, so that an interrupted run never leaves a partial
+    /// record behind.
+    pub fn save(&self, save_to: &Path) -> Result<()> {
+        let mut file = NamedTempFile::new_in(save_to)?;
+        file.write_all(&serde_json::to_vec(self)?)?;
+        file.persist(save_to.join(RECORD_FILE))?.sync_all()?;
+        Ok(())
+    }
+
+    /// Get the archived post if it's not changed since last run.
+    pub fn unchanged_post(&self, id: u32, updated_at: &DateTime<Utc>) -> Option<&Post> {
+        self.index
+            .get(&id)
+            .map(|&idx| &self.posts[idx])
+            .filter(|archived| &archived.updated_at == updated_at)
+            .map(|archived| &archived.post)
+    }
+
+    /// Collect ids of posts which are new or changed since last run, including those whose likes
+    /// or reactions changed.
+    pub fn changed_posts(&self, posts: &[ArchivedPost]) -> HashSet<u32> {
+        posts
+            .iter()
+            .filter(|post| {
+                !matches!(
+                    self.unchanged_post(post.id, &post.updated_at),
+                    Some(archived) if archived.likes == post.post.likes
+                        && archived.emojis == post.post.emojis
+                )
+            })
+            .map(|post| post.id)
+            .collect()
+    }
+
+    /// Check whether pages look different from last run regardless of their posts.
+    pub fn is_layout_changed(&self, layout: &str) -> bool {
+        self.layout != layout
+    }
+
+    /// Check whether topic meta shown on every page changed since last run.
+    pub fn is_meta_changed(&self, meta: &TopicMeta) -> bool {
+        self.meta != meta_hash(meta)
+    }
+
+    /// Check whether given page needs to be rendered again.
+    ///
+    /// A page is dirty if the posts on it differ from last run, or the total page count changed.
+    pub fn is_page_dirty(
+        &self,
+        page: usize,
+        ids: &[u32],
+        total_pages: usize,
+        changed: &HashSet<u32>,
+    ) -> bool {
+        self.pages.len() != total_pages
+            || self.pages.get(page).map(Vec::as_slice) != Some(ids)
+            || ids.iter().any(|id| changed.contains(id))
+    }
+}
+
+fn meta_hash(meta: &TopicMeta) -> u64 {
+    let rendered = (
+        &meta.title,
+        &meta.description,
+        &meta.categories,
+        &meta.tags,
+        meta.private,
+        &meta.participants,
+    );
+    utils::calculate_hash(&serde_json::to_string(&rendered).expect("serializable"))
+}
+
+fn assets_key(options: &ArchiveOptions) -> String {
+    serde_json::to_string(&(&options.attachments, options.previews_only)).expect("serializable")
+}
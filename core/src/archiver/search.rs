@@ -0,0 +1,96 @@
+//! Archive of posts matching a search query.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use futures::stream::FuturesOrdered;
+use futures::TryStreamExt;
+use sanitize_filename::sanitize;
+use tokio::sync::mpsc::Sender;
+
+use crate::archiver::options::ArchiveOptions;
+use crate::archiver::post_list::{archive_post_list, context_window, PostList, TopicPosts};
+use crate::archiver::{fetchers, DownloadEvent};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::models::SearchResult;
+use crate::re_exports::reqwest::Url;
+
+/// Name of the page of a search archive.
+#[must_use]
+pub fn search_page_filename(query: &str) -> String {
+    sanitize(format!("search_{query}.html"))
+}
+
+/// Archive posts matching a search query into a single page.
+///
+/// Each matching post is shown with up to `context` posts before and after it in its topic.
+/// Posts are grouped by their topics. If a topic is archived in a subdirectory of `save_to`, its
+/// posts are linked to the full archive.
+///
+/// # Errors
+///
+/// Returns error if failed to search, fetch posts or write the page.
+/// Search archives can't be anonymized and are only available in HTML.
+pub async fn archive_search(
+    client: &Client,
+    query: &str,
+    context: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+    reporter: Sender<DownloadEvent>,
+) -> Result<()> {
+    let result = fetchers::fetch_search(client, query).await?;
+
+    let mut hits: Vec<(u32, HashSet<u32>)> = vec![];
+    let mut index: HashMap<u32, usize> = HashMap::new();
+    for post in &result.posts {
+        let idx = *index.entry(post.topic_id).or_insert_with(|| {
+            hits.push((post.topic_id, HashSet::new()));
+            hits.len() - 1
+        });
+        hits[idx].1.insert(post.id);
+    }
+
+    let futs: FuturesOrdered<_> = hits
+        .into_iter()
+        .map(|(topic_id, highlighted)| {
+            let title = topic_title(&result, topic_id);
+            async move {
+                let stream = if context == 0 {
+                    vec![]
+                } else {
+                    fetchers::fetch_post_stream(client, topic_id).await?
+                };
+                Ok::<_, Error>(TopicPosts {
+                    id: topic_id,
+                    title,
+                    note: Some(format!("{} 个结果", highlighted.len())),
+                    post_ids: context_window(&stream, &highlighted, context),
+                    highlighted,
+                })
+            }
+        })
+        .collect();
+    let topics: Vec<TopicPosts> = futs.try_collect().await?;
+
+    let source = Url::parse_with_params(&client.site().url("/search"), &[("q", query)])
+        .map_or_else(|_| client.site().url("/search"), String::from);
+    let list = PostList {
+        heading: format!("搜索：{query}"),
+        summary: format!("{} 个主题中共 {} 个结果", topics.len(), result.posts.len()),
+        source,
+        filename: search_page_filename(query),
+        topics,
+    };
+    archive_post_list(client, list, save_to, options, reporter).await
+}
+
+fn topic_title(result: &SearchResult, topic_id: u32) -> String {
+    result
+        .topics
+        .iter()
+        .find(|topic| topic.id == topic_id)
+        .map(|topic| topic.title.clone())
+        .unwrap_or_default()
+}
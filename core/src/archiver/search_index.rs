@@ -0,0 +1,49 @@
+//! Offline full-text search index of an HTML archive.
+
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::archiver::{page_filename, utils};
+use crate::error::Result;
+use crate::models::Post;
+
+/// The search index loaded by the search box on archive pages.
+///
+/// It's a script instead of JSON, because pages opened from the file system can't fetch files.
+pub const SEARCH_INDEX_FILE: &str = "search_index.js";
+
+/// Write plain text and authors of all posts into the search index.
+///
+/// Each entry is `[number, page file, username, name, text]`.
+pub fn write_search_index(posts: &[Post], pages: &[Range<usize>], save_to: &Path) -> Result<()> {
+    let entries: Vec<_> = pages
+        .iter()
+        .enumerate()
+        .flat_map(|(page, range)| {
+            let filename = page_filename(page + 1);
+            posts[range.clone()].iter().map(move |post| {
+                (
+                    post.number,
+                    filename.clone(),
+                    &post.username,
+                    &post.name,
+                    plain_text(&post.content),
+                )
+            })
+        })
+        .collect();
+    let index = format!(
+        "window.SEARCH_INDEX = {};\n",
+        serde_json::to_string(&entries)?
+    );
+    fs::write(save_to.join(SEARCH_INDEX_FILE), index)?;
+    Ok(())
+}
+
+fn plain_text(content: &str) -> String {
+    utils::summarize(content)
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+}
@@ -0,0 +1,255 @@
+//! Single self-contained HTML output.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use lol_html::html_content::ContentType;
+use lol_html::{element, rewrite_str, RewriteStrSettings};
+use regex::Captures;
+use tracing::warn;
+
+use crate::archiver::manifest::AssetManifest;
+use crate::archiver::stylesheets::{MAX_IMPORT_DEPTH, RE_REFERENCE};
+use crate::archiver::utils::mime_of;
+use crate::error::Result;
+
+pub const SINGLE_HTML_FILE: &str = "index.html";
+
+const RESOURCE_ATTRS: [&str; 4] = ["src", "href", "poster", "data-video-src"];
+
+/// Inline all local resources referenced by a rendered page, and write it into a single file.
+///
+/// Stylesheets and scripts are embedded into the page, and other resources, including those
+/// referenced by `url()` in stylesheets, are converted into data URIs. Stylesheets imported by
+/// `@import` are inlined as data URIs too, up to the same depth as they are downloaded.
+/// Resources larger than `size_limit` bytes, typically videos, are linked to their original urls
+/// instead.
+pub fn write_single_html(
+    rendered: &str,
+    assets: &BTreeMap<String, AssetManifest>,
+    size_limit: Option<u64>,
+    save_to: &Path,
+) -> Result<()> {
+    let original_urls: HashMap<&Path, &str> = assets
+        .iter()
+        .map(|(url, asset)| (asset.filename.as_path(), url.as_str()))
+        .collect();
+    let inliner = Inliner {
+        save_to,
+        original_urls,
+        size_limit,
+    };
+
+    let rule = element!("*", |el| {
+        match el.tag_name().as_str() {
+            "link" if el.get_attribute("rel").as_deref() == Some("stylesheet") => {
+                if let Some((href, css)) = el
+                    .get_attribute("href")
+                    .and_then(|href| inliner.read_text(&href).map(|css| (href, css)))
+                {
+                    let css = inliner
+                        .inline_css(&css, &href, 0)
+                        .replace("</style", r"<\/style");
+                    el.replace(&format!("<style>{css}</style>"), ContentType::Html);
+                }
+                return Ok(());
+            }
+            "script" => {
+                if let Some(js) = el
+                    .get_attribute("src")
+                    .and_then(|src| inliner.read_text(&src))
+                {
+                    el.remove_attribute("src");
+                    el.set_inner_content(&js.replace("</script", r"<\/script"), ContentType::Html);
+                }
+                return Ok(());
+            }
+            _ => {}
+        }
+        // Data URIs contain commas, which can't be put into srcset. Browsers fall back to src.
+        el.remove_attribute("srcset");
+        for attr in RESOURCE_ATTRS {
+            if let Some(inlined) = el
+                .get_attribute(attr)
+                .and_then(|value| inliner.inline(&value))
+            {
+                el.set_attribute(attr, &inlined)?;
+            }
+        }
+        Ok(())
+    });
+
+    let output = rewrite_str(
+        rendered,
+        RewriteStrSettings {
+            element_content_handlers: vec![rule],
+            ..RewriteStrSettings::default()
+        },
+    )?;
+    fs::write(save_to.join(SINGLE_HTML_FILE), output)?;
+    Ok(())
+}
+
+struct Inliner<'a> {
+    save_to: &'a Path,
+    original_urls: HashMap<&'a Path, &'a str>,
+    size_limit: Option<u64>,
+}
+
+impl Inliner<'_> {
+    /// Resolve a reference into a path relative to the archive root, if it's a local resource.
+    fn local_path(reference: &str) -> Option<PathBuf> {
+        reference
+            .starts_with("resources/")
+            .then(|| PathBuf::from(reference))
+    }
+
+    fn read_text(&self, reference: &str) -> Option<String> {
+        let path = Self::local_path(reference)?;
+        fs::read_to_string(self.save_to.join(&path))
+            .map_err(|e| warn!(?path, ?e, "Failed to read resource, not inlined"))
+            .ok()
+    }
+
+    /// Inline resources referenced by `url()` and `@import` in a stylesheet at `reference`,
+    /// which is imported `depth` levels deep.
+    fn inline_css(&self, css: &str, reference: &str, depth: usize) -> String {
+        RE_REFERENCE
+            .replace_all(css, |caps: &Captures| {
+                let import = caps.name("url").is_none() || caps.name("import").is_some();
+                let url = caps
+                    .name("url")
+                    .or_else(|| caps.name("bare"))
+                    .map_or("", |m| m.as_str());
+                // Stylesheets imported deeper are saved as they are, so they are inlined as is.
+                let inlined = resolve_css_url(reference, url).and_then(|path| {
+                    if import && depth < MAX_IMPORT_DEPTH {
+                        self.read_text(&path).map(|css| {
+                            let css = self.inline_css(&css, &path, depth + 1);
+                            format!("data:text/css;base64,{}", BASE64_STANDARD.encode(css))
+                        })
+                    } else {
+                        self.inline(&path)
+                    }
+                });
+                match inlined {
+                    Some(inlined) if import => format!(r#"@import url("{inlined}")"#),
+                    Some(inlined) => format!(r#"url("{inlined}")"#),
+                    None => caps[0].to_string(),
+                }
+            })
+            .into_owned()
+    }
+
+    /// Convert a local resource into a data URI, or link it to its original url if it's too
+    /// large.
+    fn inline(&self, reference: &str) -> Option<String> {
+        let path = Self::local_path(reference)?;
+        let full_path = self.save_to.join(&path);
+        let size = fs::metadata(&full_path)
+            .map_err(|e| warn!(?path, ?e, "Missing resource, not inlined"))
+            .ok()?
+            .len();
+        if self.size_limit.is_some_and(|limit| size > limit) {
+            return self
+                .original_urls
+                .get(path.as_path())
+                .map(|url| (*url).to_string());
+        }
+        let data = fs::read(&full_path)
+            .map_err(|e| warn!(?path, ?e, "Failed to read resource, not inlined"))
+            .ok()?;
+        Some(format!(
+            "data:{};base64,{}",
+            mime_of(&path),
+            BASE64_STANDARD.encode(data)
+        ))
+    }
+}
+
+/// Resolve a relative url in a stylesheet against the stylesheet's path.
+fn resolve_css_url(stylesheet: &str, url: &str) -> Option<String> {
+    if url.is_empty() || url.contains(':') || url.starts_with(['/', '#']) {
+        return None;
+    }
+    let url = url.split(['?', '#']).next().unwrap_or_default();
+    let mut segments: Vec<&str> = stylesheet.split('/').collect();
+    segments.pop();
+    for segment in url.split('/') {
+        match segment {
+            "" | "." => {}
+            ".." => {
+                segments.pop()?;
+            }
+            _ => segments.push(segment),
+        }
+    }
+    Some(segments.join("/"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::fs;
+
+    use base64::prelude::BASE64_STANDARD;
+    use base64::Engine;
+    use tempfile::TempDir;
+
+    use super::{resolve_css_url, Inliner};
+
+    #[test]
+    fn must_inline_nested_imports() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("resources")).unwrap();
+        fs::write(
+            dir.path().join("resources/b.css"),
+            "a { background: url(c.png) }",
+        )
+        .unwrap();
+        fs::write(dir.path().join("resources/c.png"), "png").unwrap();
+        let inliner = Inliner {
+            save_to: dir.path(),
+            original_urls: HashMap::new(),
+            size_limit: None,
+        };
+
+        let css = inliner.inline_css(r#"@import "b.css" screen;"#, "resources/a.css", 0);
+        let imported = css
+            .strip_prefix(r#"@import url("data:text/css;base64,"#)
+            .and_then(|css| css.strip_suffix(r#"") screen;"#))
+            .unwrap();
+        let imported = String::from_utf8(BASE64_STANDARD.decode(imported).unwrap()).unwrap();
+        assert_eq!(
+            imported,
+            format!(
+                r#"a {{ background: url("data:image/png;base64,{}") }}"#,
+                BASE64_STANDARD.encode("png")
+            )
+        );
+    }
+
+    #[test]
+    fn must_resolve_css_urls() {
+        assert_eq!(
+            resolve_css_url("resources/site.css", "fonts/a.woff2?v=1").as_deref(),
+            Some("resources/fonts/a.woff2")
+        );
+        assert_eq!(
+            resolve_css_url("resources/css/a.css", "../b.png").as_deref(),
+            Some("resources/b.png")
+        );
+        assert_eq!(
+            resolve_css_url("resources/a.css", "data:image/png;base64,"),
+            None
+        );
+        assert_eq!(resolve_css_url("resources/a.css", "/uploads/a.png"), None);
+        assert_eq!(
+            resolve_css_url("resources/a.css", "https://a.com/b.png"),
+            None
+        );
+    }
+}
@@ -0,0 +1,213 @@
+//! Stylesheets of the live site, in place of the embedded ones.
+
+use std::collections::HashMap;
+
+use futures::future::{try_join_all, BoxFuture};
+use futures::FutureExt;
+use once_cell::sync::Lazy;
+use regex::{Captures, Regex};
+use reqwest::Url;
+use tracing::warn;
+
+use crate::archiver::download_manager::DownloadManager;
+use crate::archiver::utils;
+use crate::error::{Error, Result};
+use crate::preloaded_store::{PreloadedStore, Stylesheet};
+
+/// Nesting depth of `@import`s to follow. Stylesheets imported deeper are saved as they are.
+pub(super) const MAX_IMPORT_DEPTH: usize = 4;
+
+/// References in stylesheets, i.e. `url()`s and `@import`s with or without `url()`.
+pub(super) static RE_REFERENCE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(
+        r#"(?P<import>@import\s+)?url\(\s*['"]?(?P<url>[^'")]*)['"]?\s*\)|@import\s+['"](?P<bare>[^'"]+)['"]"#,
+    )
+    .unwrap()
+});
+
+/// Download stylesheets linked from the homepage, returning them with local hrefs.
+///
+/// Color schemes for a system preference are skipped, because themes of the archive take care of
+/// that. Assets referenced by the stylesheets, e.g. fonts, and imported stylesheets are downloaded
+/// too, and references to them are rewritten to local files.
+///
+/// Returns no stylesheets if the homepage links none or any of them or their references fails to
+/// download, so that pages fall back to the embedded ones.
+pub async fn download_stylesheets(
+    download_manager: &DownloadManager,
+    preloaded_store: &PreloadedStore,
+) -> Vec<Stylesheet> {
+    let stylesheets = preloaded_store.stylesheets().iter().filter(|stylesheet| {
+        !stylesheet
+            .media
+            .as_ref()
+            .is_some_and(|media| media.contains("prefers-color-scheme"))
+    });
+    let futs = stylesheets.map(|stylesheet| async move {
+        let url = download_manager.site().absolute_url(&stylesheet.href);
+        let filename = download_stylesheet(download_manager, url, 0).await?;
+        Ok::<_, Error>(Stylesheet {
+            href: format!("resources/{filename}"),
+            ..stylesheet.clone()
+        })
+    });
+    match try_join_all(futs).await {
+        Ok(stylesheets) => stylesheets,
+        Err(e) => {
+            warn!(
+                ?e,
+                "Failed to download stylesheets of the site, using embedded ones"
+            );
+            vec![]
+        }
+    }
+}
+
+/// Download a stylesheet and what it references, returning its file name in resources.
+fn download_stylesheet(
+    download_manager: &DownloadManager,
+    url: String,
+    depth: usize,
+) -> BoxFuture<'_, Result<String>> {
+    async move {
+        let filename = stylesheet_filename(&url);
+        let css = download_manager.fetch_text(&url).await?;
+        let Ok(base) = Url::parse(&url) else {
+            download_manager.save_text(url, &filename, &css)?;
+            return Ok(filename);
+        };
+
+        let mut references: HashMap<String, bool> = HashMap::new();
+        for caps in RE_REFERENCE.captures_iter(&css) {
+            if let Some((target, import)) = reference(&base, &caps) {
+                *references.entry(target.to_string()).or_default() |= import;
+            }
+        }
+        let futs = references.into_iter().map(|(target, import)| async move {
+            let local = if import && depth < MAX_IMPORT_DEPTH {
+                download_stylesheet(download_manager, target.clone(), depth + 1).await?
+            } else {
+                let filename = asset_filename(&target);
+                download_manager
+                    .download_asset(target.clone(), &filename, false)
+                    .await?;
+                filename
+            };
+            Ok::<_, Error>((target, local))
+        });
+        let local: HashMap<String, String> = try_join_all(futs).await?.into_iter().collect();
+
+        // Stylesheets and their assets are all in resources, so file names are relative urls.
+        let css = RE_REFERENCE.replace_all(&css, |caps: &Captures| {
+            match reference(&base, caps).and_then(|(target, import)| {
+                local
+                    .get(target.as_str())
+                    .map(|filename| (filename, import))
+            }) {
+                Some((filename, true)) => format!(r#"@import url("{filename}")"#),
+                Some((filename, false)) => format!(r#"url("{filename}")"#),
+                None => caps[0].to_string(),
+            }
+        });
+        download_manager.save_text(url, &filename, &css)?;
+        Ok(filename)
+    }
+    .boxed()
+}
+
+/// Resolve a reference in a stylesheet at `base`, telling whether it's an import. Data URIs and
+/// fragments, e.g. of SVG filters, are not references to download.
+fn reference(base: &Url, caps: &Captures) -> Option<(Url, bool)> {
+    let (target, import) = match (caps.name("url"), caps.name("bare")) {
+        (Some(url), _) => (url.as_str(), caps.name("import").is_some()),
+        (None, Some(bare)) => (bare.as_str(), true),
+        (None, None) => return None,
+    };
+    let target = target.trim();
+    if target.is_empty() || target.starts_with('#') || target.starts_with("data:") {
+        return None;
+    }
+    base.join(target)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .map(|url| (url, import))
+}
+
+/// File name of an asset referenced by a stylesheet. It's prefixed by a hash of its URL, because
+/// fonts and images of different paths may share names.
+fn asset_filename(url: &str) -> String {
+    let name = Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back().map(String::from))
+        })
+        .unwrap_or_default();
+    format!(
+        "{:x}_{}",
+        utils::calculate_hash(&url),
+        sanitize_filename::sanitize(name)
+    )
+}
+
+/// File name of a stylesheet, from the last segment of its URL.
+///
+/// Discourse puts content hashes into stylesheet names, so different versions don't collide.
+fn stylesheet_filename(url: &str) -> String {
+    let name = Url::parse(url)
+        .ok()
+        .and_then(|url| {
+            url.path_segments()
+                .and_then(|mut segments| segments.next_back().map(String::from))
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| String::from("stylesheet"));
+    let name = sanitize_filename::sanitize(name);
+    if name.ends_with(".css") {
+        name
+    } else {
+        format!("{name}.css")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use regex::Captures;
+    use reqwest::Url;
+
+    use super::{reference, stylesheet_filename, RE_REFERENCE};
+
+    #[test]
+    fn must_name_stylesheets() {
+        assert_eq!(
+            stylesheet_filename(
+                "https://shuiyuan.sjtu.edu.cn/stylesheets/desktop_theme_3_8828b367.css?__ws=shuiyuan"
+            ),
+            "desktop_theme_3_8828b367.css"
+        );
+        assert_eq!(
+            stylesheet_filename("https://example.com/stylesheets/color_definitions"),
+            "color_definitions.css"
+        );
+    }
+
+    #[test]
+    fn must_resolve_references() {
+        let base = Url::parse("https://example.com/stylesheets/a.css").unwrap();
+        let css = r#"@import "b.css"; @import url('/c.css'); .x { background: url( ../img/d.png ); }
+            .y { background: url(data:image/png;base64,AAAA); filter: url(#e); }"#;
+        let references: Vec<_> = RE_REFERENCE
+            .captures_iter(css)
+            .filter_map(|caps: Captures| reference(&base, &caps))
+            .map(|(url, import)| (url.to_string(), import))
+            .collect();
+        assert_eq!(
+            references,
+            [
+                (String::from("https://example.com/stylesheets/b.css"), true),
+                (String::from("https://example.com/c.css"), true),
+                (String::from("https://example.com/img/d.png"), false),
+            ]
+        );
+    }
+}
@@ -1,14 +1,30 @@
+use std::borrow::Cow;
+use std::fs;
 use std::io::Cursor;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 use handlebars::no_escape;
-use handlebars::Handlebars;
 use handlebars::{handlebars_helper, html_escape};
+use handlebars::{Handlebars, RenderError};
 use once_cell::sync::Lazy;
+use serde::Serialize;
 
 use crate::error;
 
 const TEMPLATE: &str = include_str!("../../templates/index.hbs");
+const COLLECTION_TEMPLATE: &str = include_str!("../../templates/collection.hbs");
+const LIBRARY_TEMPLATE: &str = include_str!("../../templates/library.hbs");
+const POST_LIST_TEMPLATE: &str = include_str!("../../templates/post_list.hbs");
+const PARTIALS: [(&str, &str); 6] = [
+    ("styles", include_str!("../../templates/styles.hbs")),
+    ("header", include_str!("../../templates/header.hbs")),
+    ("post", include_str!("../../templates/post.hbs")),
+    ("search", include_str!("../../templates/search.hbs")),
+    ("thread", include_str!("../../templates/thread.hbs")),
+    ("theme", include_str!("../../templates/theme.hbs")),
+];
+/// Names of top-level templates. Other templates in a template directory are partials.
+const TEMPLATE_NAMES: [&str; 4] = ["index", "collection", "post_list", "library"];
 const RESOURCES: &[u8] = include_bytes!("../../resources.tar.gz");
 
 handlebars_helper!(escape: | x: String | html_escape( & x));
@@ -18,14 +34,164 @@ pub static HANDLEBARS: Lazy<Handlebars<'_>> = Lazy::new(|| {
     handlebars.register_escape_fn(no_escape);
     handlebars.set_strict_mode(true);
     handlebars.register_helper("escape", Box::new(escape));
+    for (name, partial) in PARTIALS {
+        handlebars.register_partial(name, partial).unwrap();
+    }
     handlebars
         .register_template_string("index", TEMPLATE)
         .unwrap();
     handlebars
+        .register_template_string("collection", COLLECTION_TEMPLATE)
+        .unwrap();
+    handlebars
+        .register_template_string("post_list", POST_LIST_TEMPLATE)
+        .unwrap();
+    handlebars
+        .register_template_string("library", LIBRARY_TEMPLATE)
+        .unwrap();
+    handlebars
 });
 
-pub fn extract_resources(to: impl AsRef<Path>) -> error::Result<()> {
+/// Templates to render pages with, overridden by those in given template directory if any.
+///
+/// A template directory may contain:
+/// - `*.hbs`: templates named after their file stems, e.g. `index.hbs` or `post.hbs`. Files not
+///   named after a top-level template are registered as partials, so new partials can be added
+///   too.
+/// - `helpers/*.rhai`: [script helpers](https://docs.rs/handlebars/4/handlebars/#script-helper)
+///   named after their file stems.
+/// - `resources/`: files copied over the embedded resources.
+///
+/// All templates and helpers are compiled here, so that broken ones are reported before archiving
+/// starts.
+///
+/// # Errors
+///
+/// Returns error if failed to read or compile any template or helper.
+pub fn handlebars(template_dir: Option<&Path>) -> error::Result<Cow<'static, Handlebars<'static>>> {
+    let Some(dir) = template_dir else {
+        return Ok(Cow::Borrowed(&HANDLEBARS));
+    };
+    let mut handlebars = HANDLEBARS.clone();
+    for path in sorted_files(dir, "hbs")? {
+        let name = file_stem(&path);
+        if TEMPLATE_NAMES.contains(&name.as_str()) {
+            handlebars
+                .register_template_file(&name, &path)
+                .map_err(|e| invalid(&path, e))?;
+        } else {
+            let partial = fs::read_to_string(&path)?;
+            handlebars
+                .register_partial(&name, partial)
+                .map_err(|e| invalid(&path, e))?;
+        }
+    }
+    let helpers = dir.join("helpers");
+    if helpers.is_dir() {
+        for path in sorted_files(&helpers, "rhai")? {
+            handlebars
+                .register_script_helper_file(&file_stem(&path), &path)
+                .map_err(|e| invalid(&path, e))?;
+        }
+    }
+    Ok(Cow::Owned(handlebars))
+}
+
+/// Compiled templates, for commands which render pages after archiving many topics, so that
+/// broken templates are reported before anything is archived.
+pub struct Templates(Cow<'static, Handlebars<'static>>);
+
+impl Templates {
+    /// Compile templates, overridden by those in given template directory if any.
+    ///
+    /// # Errors
+    ///
+    /// Returns error if failed to read or compile any template or helper.
+    pub fn load(template_dir: Option<&Path>) -> error::Result<Self> {
+        Ok(Self(handlebars(template_dir)?))
+    }
+
+    pub(crate) fn render(&self, name: &str, data: &impl Serialize) -> error::Result<String> {
+        Ok(self.0.render(name, data)?)
+    }
+}
+
+fn invalid(path: &Path, e: impl std::error::Error + Send + Sync + 'static) -> RenderError {
+    RenderError::from_error(&path.display().to_string(), e)
+}
+
+fn sorted_files(dir: &Path, extension: &str) -> error::Result<Vec<PathBuf>> {
+    let mut files = vec![];
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_file() && path.extension().is_some_and(|ext| ext == extension) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+fn file_stem(path: &Path) -> String {
+    path.file_stem()
+        .expect("is a file")
+        .to_string_lossy()
+        .into_owned()
+}
+
+/// Extract embedded resources, then copy resources from given template directory over them.
+pub fn extract_resources(to: impl AsRef<Path>, template_dir: Option<&Path>) -> error::Result<()> {
+    let to = to.as_ref();
     let mut archive = tar::Archive::new(flate2::read::GzDecoder::new(Cursor::new(RESOURCES)));
     archive.unpack(to)?;
+    if let Some(resources) = template_dir.map(|dir| dir.join("resources")) {
+        if resources.is_dir() {
+            copy_dir(&resources, to)?;
+        }
+    }
+    Ok(())
+}
+
+fn copy_dir(from: &Path, to: &Path) -> error::Result<()> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            fs::copy(entry.path(), target)?;
+        }
+    }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    use crate::error::Error;
+
+    use super::handlebars;
+
+    #[test]
+    fn must_override_templates() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("library.hbs"), "{{> banner}}{{total}}").unwrap();
+        fs::write(dir.path().join("banner.hbs"), "SJTU ").unwrap();
+        let registry = handlebars(Some(dir.path())).unwrap();
+        assert_eq!(
+            registry.render("library", &json!({"total": 3})).unwrap(),
+            "SJTU 3"
+        );
+
+        fs::write(dir.path().join("post.hbs"), "{{#if}}").unwrap();
+        assert!(matches!(
+            handlebars(Some(dir.path())),
+            Err(Error::Handlebars(_))
+        ));
+    }
+}
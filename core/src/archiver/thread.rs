@@ -0,0 +1,116 @@
+//! Reply trees of posts.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::archiver::page_filename;
+use crate::models::{Post, ThreadPosition};
+
+/// Order posts depth-first along their reply trees, returning indices of posts in the new order
+/// along with their positions in the trees.
+///
+/// Replies follow their parents in chronological order. Posts replying to a missing post, e.g. a
+/// deleted one, start new trees.
+pub fn thread_order(posts: &[Post]) -> Vec<(usize, ThreadPosition)> {
+    let index_of: HashMap<usize, usize> = posts
+        .iter()
+        .enumerate()
+        .map(|(idx, post)| (post.number, idx))
+        .collect();
+    let parent_of = |post: &Post| {
+        post.reply_to
+            .and_then(|number| index_of.get(&number).copied())
+            // Guard against cycles.
+            .filter(|parent| posts[*parent].number < post.number)
+    };
+
+    let mut children: Vec<Vec<usize>> = vec![vec![]; posts.len()];
+    let mut roots = vec![];
+    for (idx, post) in posts.iter().enumerate() {
+        match parent_of(post) {
+            Some(parent) => children[parent].push(idx),
+            None => roots.push(idx),
+        }
+    }
+
+    let mut order = Vec::with_capacity(posts.len());
+    let mut stack: Vec<(usize, usize)> = roots.into_iter().rev().map(|idx| (idx, 0)).collect();
+    while let Some((idx, depth)) = stack.pop() {
+        order.push((
+            idx,
+            ThreadPosition {
+                depth,
+                replies: children[idx].len(),
+            },
+        ));
+        stack.extend(children[idx].iter().rev().map(|child| (*child, depth + 1)));
+    }
+    order
+}
+
+/// Link each reply to its parent, which may be on another page.
+pub fn link_replies(posts: &mut [Post], pages: &[Range<usize>]) {
+    let page_of: HashMap<usize, usize> = pages
+        .iter()
+        .enumerate()
+        .flat_map(|(page, range)| posts[range.clone()].iter().map(move |p| (p.number, page)))
+        .collect();
+    for (page, range) in pages.iter().enumerate() {
+        for post in &mut posts[range.clone()] {
+            post.reply_to_link = post.reply_to.and_then(|number| {
+                let parent_page = *page_of.get(&number)?;
+                Some(if parent_page == page {
+                    format!("#post_{number}")
+                } else {
+                    format!("{}#post_{number}", page_filename(parent_page + 1))
+                })
+            });
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::models::Post;
+
+    use super::{link_replies, thread_order};
+
+    fn post(number: usize, reply_to: Option<usize>) -> Post {
+        Post {
+            number,
+            reply_to,
+            ..Post::default()
+        }
+    }
+
+    #[test]
+    fn must_order_threads() {
+        // 1 ─┬─ 2 ─── 4
+        //    └─ 3
+        // 5 (replying to a deleted post)
+        let posts = [
+            post(1, None),
+            post(2, Some(1)),
+            post(3, Some(1)),
+            post(4, Some(2)),
+            post(5, Some(100)),
+        ];
+        let order: Vec<_> = thread_order(&posts)
+            .into_iter()
+            .map(|(idx, pos)| (posts[idx].number, pos.depth, pos.replies))
+            .collect();
+        assert_eq!(
+            order,
+            [(1, 0, 2), (2, 1, 1), (4, 2, 0), (3, 1, 0), (5, 0, 0)]
+        );
+    }
+
+    #[test]
+    fn must_link_replies_across_pages() {
+        let mut posts = [post(1, None), post(2, Some(1)), post(3, Some(1))];
+        link_replies(&mut posts, &[0..2, 2..3]);
+        assert_eq!(posts[0].reply_to_link, None);
+        assert_eq!(posts[1].reply_to_link.as_deref(), Some("#post_1"));
+        assert_eq!(posts[2].reply_to_link.as_deref(), Some("index.html#post_1"));
+    }
+}
@@ -0,0 +1,103 @@
+//! Archive of all topics created and posts written by a user.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+
+use futures::stream::FuturesOrdered;
+use futures::TryStreamExt;
+use sanitize_filename::sanitize;
+use tokio::sync::mpsc::Sender;
+
+use crate::archiver::options::ArchiveOptions;
+use crate::archiver::post_list::{archive_post_list, context_window, PostList, TopicPosts};
+use crate::archiver::{fetchers, DownloadEvent};
+use crate::client::Client;
+use crate::error::{Error, Result};
+use crate::models::UserAction;
+
+/// Name of the page of a user's archive.
+#[must_use]
+pub fn user_page_filename(username: &str) -> String {
+    sanitize(format!("user_{username}.html"))
+}
+
+/// Archive all topics created and posts written by a user into a single page.
+///
+/// Each post is shown with up to `context` posts before and after it in its topic.
+/// Posts are grouped by their topics. If a topic is archived in a subdirectory of `save_to`, its
+/// posts are linked to the full archive.
+///
+/// # Errors
+///
+/// Returns error if the user is not accessible, or failed to fetch posts or write the page.
+/// User archives can't be anonymized and are only available in HTML.
+pub async fn archive_user(
+    client: &Client,
+    username: &str,
+    context: usize,
+    save_to: &Path,
+    options: &ArchiveOptions,
+    reporter: Sender<DownloadEvent>,
+) -> Result<()> {
+    let profile = fetchers::fetch_user(client, username).await?;
+    let actions = fetchers::fetch_user_actions(client, username).await?;
+    let futs: FuturesOrdered<_> = group_by_topic(actions)
+        .into_iter()
+        .map(|topic| async move {
+            let stream = if context == 0 {
+                vec![]
+            } else {
+                fetchers::fetch_post_stream(client, topic.id).await?
+            };
+            Ok::<_, Error>(TopicPosts {
+                post_ids: context_window(&stream, &topic.highlighted, context),
+                ..topic
+            })
+        })
+        .collect();
+    let topics: Vec<TopicPosts> = futs.try_collect().await?;
+
+    let heading = match profile.name.filter(|name| !name.is_empty()) {
+        Some(name) => format!("{} ({name})", profile.username),
+        None => profile.username,
+    };
+    let created = topics.iter().filter(|t| t.note.is_some()).count();
+    let total: usize = topics.iter().map(|t| t.highlighted.len()).sum();
+    let list = PostList {
+        heading,
+        summary: format!("创建了 {created} 个主题，共 {total} 个贴子"),
+        source: client.site().url(&format!("/u/{username}/activity")),
+        filename: user_page_filename(username),
+        topics,
+    };
+    archive_post_list(client, list, save_to, options, reporter).await
+}
+
+/// Group actions by topic, keeping the order in which topics first appear.
+///
+/// Posts of the user are highlighted. Posts to show are left for the caller to fill in.
+fn group_by_topic(actions: Vec<UserAction>) -> Vec<TopicPosts> {
+    let mut topics: Vec<TopicPosts> = vec![];
+    let mut index: HashMap<u32, usize> = HashMap::new();
+    for action in actions {
+        let idx = *index.entry(action.topic_id).or_insert_with(|| {
+            topics.push(TopicPosts {
+                id: action.topic_id,
+                title: action.title.clone(),
+                note: None,
+                post_ids: vec![],
+                highlighted: HashSet::new(),
+            });
+            topics.len() - 1
+        });
+        let topic = &mut topics[idx];
+        // 4: new topic
+        if action.action_type == 4 {
+            topic.note = Some(String::from("主题作者"));
+        }
+        if let Some(post_id) = action.post_id {
+            topic.highlighted.insert(post_id);
+        }
+    }
+    topics
+}
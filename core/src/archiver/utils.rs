@@ -1,6 +1,8 @@
+use std::borrow::Cow;
 use std::collections::hash_map::DefaultHasher;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
+use std::path::Path;
 
 use chrono::{DateTime, Local, Utc};
 use html2text::render::text_renderer::TrivialDecorator;
@@ -36,3 +38,128 @@ pub fn datetime_to_display(datetime: &DateTime<Utc>) -> String {
         .format("%Y年%m月%d日 %H:%M")
         .to_string()
 }
+
+/// Extensions and MIME types of known files. The first extension of a MIME type is its canonical
+/// one, and the first MIME type of an extension is its canonical one.
+const MIME_TYPES: [(&str, &str); 27] = [
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("gif", "image/gif"),
+    ("webp", "image/webp"),
+    ("avif", "image/avif"),
+    ("svg", "image/svg+xml"),
+    ("heic", "image/heic"),
+    ("ico", "image/x-icon"),
+    ("mp4", "video/mp4"),
+    ("mov", "video/quicktime"),
+    ("avi", "video/x-msvideo"),
+    ("webm", "video/webm"),
+    ("mkv", "video/x-matroska"),
+    ("mp3", "audio/mpeg"),
+    ("mp3", "audio/mp3"),
+    ("m4a", "audio/mp4"),
+    ("m4a", "audio/x-m4a"),
+    ("ogg", "audio/ogg"),
+    ("ogg", "video/ogg"),
+    ("wav", "audio/wav"),
+    ("wav", "audio/x-wav"),
+    ("wav", "audio/wave"),
+    ("wav", "audio/vnd.wave"),
+    ("webm", "audio/webm"),
+    ("css", "text/css"),
+    ("js", "text/javascript"),
+];
+
+/// Guess the MIME type of a file from its extension.
+pub fn mime_of(path: &Path) -> &'static str {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .and_then(mime_of_extension)
+        .unwrap_or("application/octet-stream")
+}
+
+fn mime_of_extension(ext: &str) -> Option<&'static str> {
+    MIME_TYPES
+        .iter()
+        .find(|(known, _)| known.eq_ignore_ascii_case(ext))
+        .map(|(_, mime)| *mime)
+}
+
+/// Whether a file of given extension is an image, a video or an audio.
+pub fn is_media_extension(ext: &str) -> bool {
+    mime_of_extension(ext).is_some_and(is_media_mime)
+}
+
+/// Extension of a media file served with given `Content-Type`, or `None` if it's not media.
+///
+/// Types not in the known table fall back to their subtype, e.g. `image/jxl` gives `jxl`.
+pub fn media_extension(content_type: &str) -> Option<Cow<'static, str>> {
+    let mime = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    if !is_media_mime(&mime) {
+        return None;
+    }
+    if let Some((ext, _)) = MIME_TYPES.iter().find(|(_, known)| *known == mime) {
+        return Some(Cow::Borrowed(*ext));
+    }
+    let subtype = mime.split_once('/').map(|(_, subtype)| subtype)?;
+    let subtype = subtype.strip_prefix("x-").unwrap_or(subtype);
+    let subtype = subtype.strip_prefix("vnd.").unwrap_or(subtype);
+    let ext: String = subtype
+        .split('+')
+        .next()
+        .unwrap_or_default()
+        .chars()
+        .filter(char::is_ascii_alphanumeric)
+        .collect();
+    (!ext.is_empty()).then_some(Cow::Owned(ext))
+}
+
+fn is_media_mime(mime: &str) -> bool {
+    ["image/", "video/", "audio/"]
+        .iter()
+        .any(|kind| mime.starts_with(kind))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use super::{is_media_extension, media_extension, mime_of};
+
+    #[test]
+    fn must_guess_mime() {
+        assert_eq!(mime_of(Path::new("resources/a.PNG")), "image/png");
+        assert_eq!(mime_of(Path::new("resources/a_b.c.mp4")), "video/mp4");
+        assert_eq!(
+            mime_of(Path::new("resources/a")),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn must_detect_media() {
+        assert!(is_media_extension("WEBP"));
+        assert!(is_media_extension("m4a"));
+        assert!(!is_media_extension("pdf"));
+        assert_eq!(media_extension("image/svg+xml").as_deref(), Some("svg"));
+        assert_eq!(
+            media_extension("audio/x-wav; charset=binary").as_deref(),
+            Some("wav")
+        );
+        assert_eq!(media_extension("text/html; charset=utf-8"), None);
+        assert_eq!(media_extension("video/"), None);
+    }
+
+    #[test]
+    fn must_fall_back_to_mime_subtype() {
+        assert_eq!(media_extension("image/jxl").as_deref(), Some("jxl"));
+        assert_eq!(media_extension("audio/FLAC").as_deref(), Some("flac"));
+        assert_eq!(media_extension("video/x-flv").as_deref(), Some("flv"));
+    }
+}
@@ -26,6 +26,7 @@ use uuid::Uuid;
 use crate::atomic_file::AtomicFile;
 use crate::error::{Error, Result};
 use crate::middleware::{BypassThrottle, RetryMiddleware, TimeoutMiddleware};
+use crate::site::Site;
 
 pub const MAX_CONN: usize = 4;
 pub const LOOSE_MAX_CONN: usize = 64;
@@ -52,9 +53,9 @@ fn generate_client_id(app_id: &Uuid) -> String {
     client_id.to_string()
 }
 
-/// Generate the OAuth URL from given app ID and public key.
+/// Generate the OAuth URL of given site from given app ID and public key.
 #[must_use]
-pub fn oauth_url(app_id: &Uuid, key: &RsaPublicKey, callback: bool) -> String {
+pub fn oauth_url(site: &Site, app_id: &Uuid, key: &RsaPublicKey, callback: bool) -> String {
     let client_id = generate_client_id(app_id);
     let pubkey = key
         .to_pkcs1_pem(Default::default())
@@ -70,7 +71,7 @@ pub fn oauth_url(app_id: &Uuid, key: &RsaPublicKey, callback: bool) -> String {
         query.push(("auth_redirect", "discourse://auth_redirect"));
     }
     let parsed_query = serde_urlencoded::to_string(query).expect("failed to encode query");
-    format!("https://shuiyuan.sjtu.edu.cn/user-api-key/new?{parsed_query}")
+    site.url(&format!("/user-api-key/new?{parsed_query}"))
 }
 
 /// Unpack the OAuth token from the given payload.
@@ -186,6 +187,7 @@ impl IntoRequestBuilderWrapped for RequestBuilder {
 #[derive(Clone)]
 pub struct Client {
     client: ClientWithMiddleware,
+    site: Arc<Site>,
     loose_sem: Arc<Semaphore>,
     sem: Arc<Semaphore>,
     bucket: Arc<RateLimiter>,
@@ -194,6 +196,7 @@ pub struct Client {
 impl Debug for Client {
     fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
         f.debug_struct("Client")
+            .field("site", &self.site)
             .field("loose_sem", &self.loose_sem)
             .field("sem", &self.sem)
             .field("bucket", &"RateLimiter")
@@ -210,6 +213,11 @@ impl Deref for Client {
 }
 
 impl Client {
+    /// The site this client is logged in to.
+    #[must_use]
+    pub fn site(&self) -> &Site {
+        &self.site
+    }
     /// Send a request and return the json response.
     ///
     /// This method applies rate limiting and connection limiting, and retries on failure.
@@ -281,12 +289,13 @@ impl Client {
     }
 }
 
-/// Create a client with given token.
+/// Create a client of given site with given token.
 ///
 /// # Errors
 ///
 /// Errors if an http client can't be created, or the token is illegal.
 pub async fn create_client_with_token(
+    site: Site,
     token: &str,
     rate_limit_callback: impl 'static + Fn(u64) + Send + Sync,
 ) -> Result<Client> {
@@ -307,12 +316,13 @@ pub async fn create_client_with_token(
         .build();
 
     client
-        .get("https://shuiyuan.sjtu.edu.cn/session/current.json")
+        .get(site.url("/session/current.json"))
         .send()
         .await?
         .error_for_status()?;
     Ok(Client {
         client,
+        site: Arc::new(site),
         loose_sem: Arc::new(Semaphore::new(LOOSE_MAX_CONN)),
         sem: Arc::new(Semaphore::new(MAX_CONN)),
         bucket: Arc::new(
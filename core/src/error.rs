@@ -38,6 +38,10 @@ pub enum Error {
     AtomicFileWrite(#[from] PersistError),
     #[error("rewriting error: {0}")]
     Rewriting(#[from] RewritingError),
+    #[error("zip error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    #[error("unsupported options: {0}")]
+    UnsupportedOptions(&'static str),
 }
 
 fn classify_reqwest_error(e: &reqwest::Error) -> bool {
@@ -12,6 +12,7 @@ mod middleware;
 mod models;
 mod preloaded_store;
 mod shared_promise;
+pub mod site;
 
 pub mod re_exports {
     pub use reqwest;
@@ -1,17 +1,46 @@
 use std::collections::HashMap;
 use std::path::PathBuf;
+use std::sync::Arc;
 
 use chrono::{DateTime, Datelike, Local, Utc};
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 use typeshare::typeshare;
 
+use crate::archiver::Theme;
+use crate::preloaded_store::Stylesheet;
+use crate::site::Site;
+
 #[derive(Debug, Deserialize)]
 pub struct RespTopic {
     pub title: String,
-    pub category_id: usize,
+    /// Private messages have no category.
+    pub category_id: Option<usize>,
     pub tags: Vec<String>,
     pub post_stream: PostStream,
     pub posts_count: usize,
+    #[serde(default)]
+    pub archetype: String,
+    #[serde(default)]
+    pub details: Option<RespTopicDetails>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespTopicDetails {
+    #[serde(default)]
+    pub allowed_users: Vec<RespParticipant>,
+    #[serde(default)]
+    pub allowed_groups: Vec<RespParticipantGroup>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespParticipant {
+    pub username: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespParticipantGroup {
+    pub name: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -35,6 +64,181 @@ pub struct RespCategoryInner {
     #[serde(flatten)]
     pub inner: Category,
     pub parent_category_id: Option<usize>,
+    #[serde(default)]
+    pub slug: String,
+    #[serde(default)]
+    pub description_text: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespSite {
+    #[serde(default)]
+    pub categories: Vec<RespSiteCategory>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespSiteCategory {
+    pub id: usize,
+    #[serde(default)]
+    pub slug: String,
+    pub parent_category_id: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespTopicList {
+    pub topic_list: TopicList,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopicList {
+    pub topics: Vec<TopicSummary>,
+    pub more_topics_url: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespUserActions {
+    #[serde(default)]
+    pub user_actions: Vec<UserAction>,
+}
+
+/// A topic created or a post written by a user.
+#[derive(Debug, Clone, Deserialize)]
+pub struct UserAction {
+    pub action_type: u32,
+    pub topic_id: u32,
+    pub post_id: Option<u32>,
+    pub post_number: usize,
+    pub title: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespUser {
+    pub user: UserProfile,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserProfile {
+    pub username: String,
+    #[serde(default)]
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespSearch {
+    #[serde(default)]
+    pub posts: Vec<SearchPost>,
+    #[serde(default)]
+    pub topics: Vec<TopicSummary>,
+    pub grouped_search_result: Option<GroupedSearchResult>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GroupedSearchResult {
+    #[serde(default)]
+    pub more_full_page_results: Option<bool>,
+}
+
+/// A post matching a search query.
+#[derive(Debug, Clone, Deserialize)]
+pub struct SearchPost {
+    pub id: u32,
+    pub topic_id: u32,
+    pub post_number: usize,
+}
+
+/// Topics and posts matching a search query, in the order of relevance.
+#[derive(Debug, Clone, Default)]
+pub struct SearchResult {
+    pub topics: Vec<TopicSummary>,
+    pub posts: Vec<SearchPost>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespCurrentUser {
+    pub current_user: UserProfile,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RespBookmarks {
+    /// Missing if the user has no bookmarks.
+    #[serde(default)]
+    pub user_bookmark_list: Option<BookmarkList>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BookmarkList {
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+    pub more_bookmarks_url: Option<String>,
+}
+
+/// A bookmark of a topic or a post.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Bookmark {
+    pub id: u32,
+    #[serde(default)]
+    pub name: Option<String>,
+    #[serde(default)]
+    pub reminder_at: Option<DateTime<Utc>>,
+    /// "Post" or "Topic".
+    pub bookmarkable_type: String,
+    /// Id of the bookmarked post or topic.
+    pub bookmarkable_id: u32,
+    pub topic_id: u32,
+    pub title: String,
+    #[serde(default)]
+    pub linked_post_number: Option<usize>,
+    #[serde(default)]
+    pub highest_post_number: usize,
+    #[serde(default)]
+    pub bumped_at: Option<DateTime<Utc>>,
+}
+
+impl Bookmark {
+    /// Id of the bookmarked post, if a post is bookmarked instead of a topic.
+    #[must_use]
+    pub fn post_id(&self) -> Option<u32> {
+        (self.bookmarkable_type == "Post").then_some(self.bookmarkable_id)
+    }
+}
+
+/// A category to archive topics from.
+#[derive(Debug, Clone)]
+pub struct CategoryInfo {
+    pub id: usize,
+    pub name: String,
+    pub slug: String,
+    pub description: Option<String>,
+}
+
+impl CategoryInfo {
+    /// Path of the category on the site.
+    #[must_use]
+    pub fn path(&self) -> String {
+        category_path(&self.slug, self.id)
+    }
+}
+
+/// Path of a category on the site. Categories without a slug are addressed by id only.
+pub fn category_path(slug: &str, id: usize) -> String {
+    if slug.is_empty() {
+        format!("/c/{id}")
+    } else {
+        format!("/c/{slug}/{id}")
+    }
+}
+
+/// A topic in a listing, e.g. of a category or a tag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopicSummary {
+    pub id: u32,
+    pub title: String,
+    #[serde(default)]
+    pub category_id: Option<usize>,
+    #[serde(default)]
+    pub posts_count: usize,
+    #[serde(default)]
+    pub last_posted_at: Option<DateTime<Utc>>,
 }
 
 #[typeshare]
@@ -44,7 +248,7 @@ pub struct Category {
     pub color: String,
 }
 
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct Post {
     pub name: String,
     pub number: usize,
@@ -56,6 +260,22 @@ pub struct Post {
     pub reply_to: Option<usize>,
     pub emojis: HashMap<String, usize>,
     pub avatar: Option<PathBuf>,
+    /// Raw source with upload links pointing to local files. Only fetched for Markdown output.
+    #[serde(default)]
+    pub raw: Option<String>,
+    /// Link to the post replied to, relative to the page this post is on.
+    #[serde(default)]
+    pub reply_to_link: Option<String>,
+    /// Position in the reply tree, if rendered threaded.
+    #[serde(default)]
+    pub thread: Option<ThreadPosition>,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ThreadPosition {
+    pub depth: usize,
+    /// Number of direct replies.
+    pub replies: usize,
 }
 
 #[derive(Debug, Deserialize)]
@@ -65,6 +285,7 @@ pub struct RespPost {
     pub name: String,
     pub username: String,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
     pub cooked: String,
     #[serde(default)]
     pub cooked_hidden: bool,
@@ -73,6 +294,9 @@ pub struct RespPost {
     pub retorts: Vec<RespRetort>,
     pub avatar_template: String,
     pub action_code: Option<String>,
+    /// Subject of the action, e.g. the user invited to a private message.
+    #[serde(default)]
+    pub action_code_who: Option<String>,
     #[serde(default)]
     pub polls: Vec<RespPoll>,
 }
@@ -116,26 +340,37 @@ pub struct Topic<'a> {
     pub description: String,
     pub categories: Vec<Category>,
     pub tags: Vec<String>,
+    pub private: bool,
+    pub participants: Vec<String>,
     pub posts: &'a [Post],
     pub page: usize,
     pub total_pages: usize,
     pub prev_page: Option<String>,
     // can be "index"
     pub next_page: Option<usize>,
+    /// Whether a search index is written along with the page.
+    pub search_index: bool,
+    /// Whether posts are shown along their reply trees.
+    pub threaded: bool,
+    pub theme: Theme,
+    /// Stylesheets of the live site. The embedded ones are used if empty.
+    pub stylesheets: &'a [Stylesheet],
 }
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Params<'a> {
     #[serde(flatten)]
     pub topic: Topic<'a>,
+    pub site: &'a Site,
     pub app_version: String,
     pub year: i32,
 }
 
-impl<'a> From<Topic<'a>> for Params<'a> {
-    fn from(t: Topic<'a>) -> Self {
+impl<'a> Params<'a> {
+    pub fn new(t: Topic<'a>, site: &'a Site) -> Self {
         Self {
             topic: t,
+            site,
             app_version: env!("CARGO_PKG_VERSION").to_string(),
             year: Local::now().year(),
         }
@@ -143,7 +378,7 @@ impl<'a> From<Topic<'a>> for Params<'a> {
 }
 
 #[typeshare]
-#[derive(Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "kebab-case")]
 pub struct TopicMeta {
     pub id: u32,
@@ -152,4 +387,14 @@ pub struct TopicMeta {
     pub categories: Vec<Category>,
     pub tags: Vec<String>,
     pub post_ids: Vec<u32>,
+    /// Whether the topic is a private message.
+    #[serde(default)]
+    pub private: bool,
+    /// Users and groups allowed in a private message.
+    #[serde(default)]
+    pub participants: Vec<String>,
+    /// Unmodified response of the topic, kept for raw JSON output. It's lost once the meta is
+    /// serialized, e.g. sent to the frontend, in which case the topic is fetched again.
+    #[serde(skip)]
+    pub raw: Option<Arc<Value>>,
 }
@@ -1,10 +1,10 @@
 use std::collections::HashMap;
 
 use lol_html::{element, HtmlRewriter, RewriteStrSettings};
-use reqwest_middleware::ClientWithMiddleware;
 use serde::de::{DeserializeOwned, Error};
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serialize};
 
+use crate::client::Client;
 use crate::error::Result;
 
 #[derive(Debug, Deserialize)]
@@ -12,6 +12,19 @@ use crate::error::Result;
 pub struct PreloadedStore {
     #[serde(deserialize_with = "de_from_emojis")]
     custom_emoji: HashMap<String, String>,
+    /// Stylesheets linked from the homepage, in order.
+    #[serde(skip)]
+    stylesheets: Vec<Stylesheet>,
+}
+
+/// A stylesheet linked from a page.
+#[derive(Debug, Clone, Serialize)]
+pub struct Stylesheet {
+    pub href: String,
+    pub media: Option<String>,
+    pub target: Option<String>,
+    pub theme_id: Option<String>,
+    pub theme_name: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -21,15 +34,16 @@ pub struct Emoji {
 }
 
 impl PreloadedStore {
-    pub async fn from_client(client: &ClientWithMiddleware) -> Result<Self> {
+    pub async fn from_client(client: &Client) -> Result<Self> {
         let body = client
-            .get("https://shuiyuan.sjtu.edu.cn")
+            .get(&client.site().base_url)
             .send()
             .await?
             .text()
             .await?;
         let mut preloaded = None;
-        let rule = element!("#data-preloaded", |el| {
+        let mut stylesheets = vec![];
+        let preloaded_rule = element!("#data-preloaded", |el| {
             if preloaded
                 .replace(el.get_attribute("data-preloaded").expect("data-preloaded"))
                 .is_some()
@@ -38,9 +52,19 @@ impl PreloadedStore {
             }
             Ok(())
         });
+        let stylesheet_rule = element!(r#"link[rel="stylesheet"][href]"#, |el| {
+            stylesheets.push(Stylesheet {
+                href: el.get_attribute("href").expect("href"),
+                media: el.get_attribute("media"),
+                target: el.get_attribute("data-target"),
+                theme_id: el.get_attribute("data-theme-id"),
+                theme_name: el.get_attribute("data-theme-name"),
+            });
+            Ok(())
+        });
         let _ = HtmlRewriter::new(
             RewriteStrSettings {
-                element_content_handlers: vec![rule],
+                element_content_handlers: vec![preloaded_rule, stylesheet_rule],
                 ..RewriteStrSettings::default()
             }
             .into(),
@@ -51,11 +75,16 @@ impl PreloadedStore {
         let unescaped =
             htmlescape::decode_html(&preloaded.expect("#data-preloaded")).expect("unescaped");
 
-        Ok(serde_json::from_str(&unescaped)?)
+        let mut store: Self = serde_json::from_str(&unescaped)?;
+        store.stylesheets = stylesheets;
+        Ok(store)
     }
     pub fn custom_emoji(&self, name: &str) -> Option<&str> {
         self.custom_emoji.get(name).map(String::as_str)
     }
+    pub fn stylesheets(&self) -> &[Stylesheet] {
+        &self.stylesheets
+    }
 }
 
 fn de_from_str<'de, D, T>(deserializer: D) -> Result<T, D::Error>
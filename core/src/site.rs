@@ -0,0 +1,135 @@
+use serde::{Deserialize, Serialize};
+use typeshare::typeshare;
+
+/// Base URL of Shuiyuan BBS, the default site.
+pub const SHUIYUAN_BASE_URL: &str = "https://shuiyuan.sjtu.edu.cn";
+
+/// A Discourse instance and its per-site settings.
+#[typeshare]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Site {
+    /// Base URL of the site, without trailing slash.
+    pub base_url: String,
+    /// Display name of the site. Used in page headers and default directory names.
+    pub name: String,
+    /// Emoji set configured on the site.
+    pub emoji_set: String,
+    /// Logo file in bundled resources, if any.
+    pub logo: Option<String>,
+    /// Copyright holder shown in the page footer, if any.
+    pub copyright: Option<String>,
+}
+
+impl Default for Site {
+    fn default() -> Self {
+        Self {
+            base_url: SHUIYUAN_BASE_URL.to_string(),
+            name: String::from("水源"),
+            emoji_set: String::from("google"),
+            logo: Some(String::from("f8a88a3a02eaa06e8d2c51ea944df2f7abdc374e.png")),
+            copyright: Some(String::from("上海交通大学")),
+        }
+    }
+}
+
+impl Site {
+    /// Create a site from its base URL.
+    ///
+    /// Shuiyuan gets its own settings. Other sites are named by their host and use Discourse
+    /// defaults.
+    #[must_use]
+    pub fn new(base_url: &str) -> Self {
+        let base_url = base_url.trim().trim_end_matches('/');
+        let base_url = if base_url.contains("://") {
+            base_url.to_string()
+        } else {
+            format!("https://{base_url}")
+        };
+        if base_url == SHUIYUAN_BASE_URL {
+            return Self::default();
+        }
+        let host = base_url
+            .split_once("://")
+            .map_or(base_url.as_str(), |(_, host)| host)
+            .to_string();
+        Self {
+            base_url,
+            name: host,
+            emoji_set: String::from("twitter"),
+            logo: None,
+            copyright: None,
+        }
+    }
+
+    /// Build an URL from a path on this site.
+    #[must_use]
+    pub fn url(&self, path: &str) -> String {
+        format!("{}{path}", self.base_url)
+    }
+
+    /// Resolve a protocol-relative or site-relative URL against this site.
+    #[must_use]
+    pub fn absolute_url(&self, url: &str) -> String {
+        if url.starts_with("//") {
+            format!("https:{url}")
+        } else if url.starts_with('/') {
+            self.url(url)
+        } else {
+            url.to_string()
+        }
+    }
+
+    /// Check whether given URL points to this site. Site-relative URLs always do.
+    #[must_use]
+    pub fn owns_url(&self, url: &str) -> bool {
+        fn host(url: &str) -> &str {
+            let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+            url.split(['/', '?', '#']).next().unwrap_or_default()
+        }
+        if url.starts_with('/') && !url.starts_with("//") {
+            return true;
+        }
+        host(url.trim_start_matches('/')).eq_ignore_ascii_case(host(&self.base_url))
+    }
+
+    /// Default directory name for an archive of given topic title. Not sanitized.
+    #[must_use]
+    pub fn archive_dir_name(&self, title: &str) -> String {
+        format!("{}_{title}", self.name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Site, SHUIYUAN_BASE_URL};
+
+    #[test]
+    fn must_normalize_base_url() {
+        assert_eq!(Site::new("https://shuiyuan.sjtu.edu.cn/"), Site::default());
+        assert_eq!(Site::new("shuiyuan.sjtu.edu.cn"), Site::default());
+        let site = Site::new("meta.discourse.org/");
+        assert_eq!(site.base_url, "https://meta.discourse.org");
+        assert_eq!(site.name, "meta.discourse.org");
+    }
+
+    #[test]
+    fn must_resolve_urls() {
+        let site = Site::default();
+        assert_eq!(site.absolute_url("//a.com/b.png"), "https://a.com/b.png");
+        assert_eq!(
+            site.absolute_url("/uploads/a.png"),
+            format!("{SHUIYUAN_BASE_URL}/uploads/a.png")
+        );
+        assert_eq!(site.absolute_url("https://a.com/b"), "https://a.com/b");
+    }
+
+    #[test]
+    fn must_check_url_host() {
+        let site = Site::default();
+        assert!(site.owns_url("https://shuiyuan.sjtu.edu.cn/t/topic/1"));
+        assert!(site.owns_url("Shuiyuan.SJTU.edu.cn/t/topic/1"));
+        assert!(site.owns_url("/t/topic/1"));
+        assert!(!site.owns_url("https://meta.discourse.org/t/topic/1"));
+        assert!(!site.owns_url("//meta.discourse.org/t/topic/1"));
+    }
+}
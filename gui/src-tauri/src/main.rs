@@ -16,10 +16,11 @@ use tauri::Wry;
 use tracing_subscriber::EnvFilter;
 
 use sa_core::archiver;
-use sa_core::archiver::{fetch_topic_meta, TopicMeta};
+use sa_core::archiver::{fetch_topic_meta, ArchiveOptions, TopicMeta};
 use sa_core::client::{create_client_with_token, oauth_url, token_from_payload, Client};
 use sa_core::re_exports::rsa;
 use sa_core::re_exports::uuid::Uuid;
+use sa_core::site::Site;
 
 use crate::url_scheme::{URLScheme, URLSchemePlugin};
 
@@ -36,13 +37,26 @@ fn sanitize(s: String) -> String {
     sanitize_filename::sanitize(s)
 }
 
+#[tauri::command]
+fn archive_dir_name(title: String, client: tauri::State<'_, Mutex<Option<Client>>>) -> String {
+    let client = client.lock().unwrap().clone().expect("client");
+    sanitize_filename::sanitize(client.site().archive_dir_name(&title))
+}
+
 #[tauri::command]
 fn open_browser(
+    site: String,
     key: tauri::State<rsa::RsaPrivateKey>,
     url_scheme: tauri::State<URLScheme>,
 ) -> bool {
     let use_callback = dbg!(url_scheme.registered);
-    webbrowser::open(&oauth_url(&APP_ID, key.as_ref(), use_callback)).expect("no browser");
+    webbrowser::open(&oauth_url(
+        &Site::new(&site),
+        &APP_ID,
+        key.as_ref(),
+        use_callback,
+    ))
+    .expect("no browser");
     use_callback
 }
 
@@ -54,6 +68,7 @@ fn token_from_oauth(payload: String, key: tauri::State<rsa::RsaPrivateKey>) -> S
 #[tauri::command]
 async fn login_with_token(
     token: String,
+    site: String,
     client: tauri::State<'_, Mutex<Option<Client>>>,
     window: tauri::Window<Wry>,
 ) -> Result<(), String> {
@@ -65,7 +80,7 @@ async fn login_with_token(
         }
     };
 
-    let new_client = create_client_with_token(&token, rate_limit_callback)
+    let new_client = create_client_with_token(Site::new(&site), &token, rate_limit_callback)
         .await
         .map_err(|e| {
             sentry::capture_error(&e);
@@ -110,7 +125,11 @@ async fn archive(
             window.emit("progress-event", ev).unwrap();
         }
     });
-    if let Err(e) = archiver::archive(&client, topic_meta, &path, mask_user, tx).await {
+    let options = ArchiveOptions {
+        anonymous: mask_user,
+        ..ArchiveOptions::default()
+    };
+    if let Err(e) = archiver::archive(&client, topic_meta, &path, &options, tx).await {
         sentry::capture_error(&e);
         return Err(e.to_string());
     }
@@ -143,6 +162,7 @@ fn main() {
         .manage(client)
         .invoke_handler(tauri::generate_handler![
             sanitize,
+            archive_dir_name,
             login_with_token,
             open_browser,
             token_from_oauth,
@@ -14,8 +14,106 @@ export interface TopicMeta {
 	categories: Category[];
 	tags: string[];
 	"post-ids": number[];
+	/** Whether the topic is a private message. */
+	private?: boolean;
+	/** Users and groups allowed in a private message. */
+	participants?: string[];
 }
 
+/** A Discourse instance and its per-site settings. */
+export interface Site {
+	/** Base URL of the site, without trailing slash. */
+	base_url: string;
+	/** Display name of the site. Used in page headers and default directory names. */
+	name: string;
+	/** Emoji set configured on the site. */
+	emoji_set: string;
+	/** Logo file in bundled resources, if any. */
+	logo?: string;
+	/** Copyright holder shown in the page footer, if any. */
+	copyright?: string;
+}
+
+/** Options of an archive. */
+export interface ArchiveOptions {
+	/** Whether to anonymize usernames. */
+	anonymous: boolean;
+	format: ArchiveFormat;
+	/**
+	 * Resources larger than this (in MiB) are linked to the site instead of being inlined in
+	 * single-file output. No limit if not set.
+	 */
+	"inline-limit-mib"?: number;
+	/** How posts are split into pages. Only applies to HTML and EPUB output. */
+	pagination?: Pagination;
+	/** Whether to show posts along their reply trees. Only applies to HTML output. */
+	threaded?: boolean;
+	/** Directory of templates, helpers and resources overriding the embedded ones. */
+	"template-dir"?: string;
+	/** Color theme the pages open with. Only applies to HTML output. */
+	theme?: Theme;
+	/**
+	 * Whether to use stylesheets of the live site instead of the embedded ones, which may be
+	 * outdated. Only applies to HTML output.
+	 */
+	"live-styles"?: boolean;
+	attachments?: AttachmentOptions;
+	/**
+	 * Whether to archive only downscaled previews of images, instead of also the originals
+	 * they open in full size.
+	 */
+	"previews-only"?: boolean;
+}
+
+/**
+ * Which attachments, i.e. uploaded files other than images and videos, are downloaded.
+ * Attachments not downloaded are linked to the site.
+ */
+export interface AttachmentOptions {
+	/** Whether to download attachments. */
+	download: boolean;
+	/** Attachments larger than this (in MiB) are not downloaded. No limit if not set. */
+	"size-limit-mib"?: number;
+	/** Extensions of attachments to download, case-insensitive. All extensions if empty. */
+	allow?: string[];
+	/** Extensions of attachments never to download, case-insensitive. */
+	deny?: string[];
+}
+
+/** Output format of an archive. */
+export enum ArchiveFormat {
+	/** Paginated HTML pages rendered from the bundled template. */
+	Html = "html",
+	/** A single Markdown file built from raw post sources. */
+	Markdown = "markdown",
+	/** Unmodified API responses, along with post contents whose asset urls are rewritten. */
+	Json = "json",
+	/** A single HTML file with all resources inlined. */
+	SingleHtml = "single-html",
+	/** An EPUB 3 book with a chapter per page. */
+	Epub = "epub",
+}
+
+/** Color theme of HTML output. Readers can switch themes in the pages. */
+export enum Theme {
+	Light = "light",
+	Dark = "dark",
+	/** Follow the color scheme of the system. */
+	Auto = "auto",
+}
+
+/** How posts are split into pages of paginated output. */
+export type Pagination = 
+	/** At most given number of posts on a page. */
+	| { mode: "posts", value: number }
+	/** All posts on one page. */
+	| { mode: "single", value?: undefined }
+	/**
+	 * Pages of at most given size in MiB, counting post contents and the local resources they
+	 * use. A post larger than that gets a page of its own.
+	 */
+	| { mode: "size", value: number };
+
 /** Download events. */
 export type DownloadEvent = 
 	/** Total post chunks to download. It's determined once metadata is fetched. */
@@ -1,15 +1,15 @@
 import {invoke} from "@tauri-apps/api";
 import {TopicMeta} from "./bindings";
 
-export const openBrowser = async () => {
-  return await invoke<boolean>("open_browser");
+export const openBrowser = async (site: string) => {
+  return await invoke<boolean>("open_browser", {site});
 }
 export const tokenFromOauth = async (payload: string) => {
   return await invoke<string>("token_from_oauth", {payload});
 }
 
-export const loginWithToken = async (token: string) => {
-  await invoke<void>("login_with_token", {token});
+export const loginWithToken = async (token: string, site: string) => {
+  await invoke<void>("login_with_token", {token, site});
 }
 
 export const fetchMeta = async (topicId: number) => {
@@ -23,3 +23,7 @@ export const archive = async (topicMeta: TopicMeta, saveTo: string, maskUser: bo
 export const sanitize = async (s: string) => {
   return await invoke<string>("sanitize", {s});
 }
+
+export const archiveDirName = async (title: string) => {
+  return await invoke<string>("archive_dir_name", {title});
+}
@@ -49,6 +49,32 @@ export const tokenState = atom({
     ]
 });
 
+export const DEFAULT_SITE = "https://shuiyuan.sjtu.edu.cn";
+export const siteState = atom({
+  key: "site",
+  default: DEFAULT_SITE,
+  effects: [
+    ({setSelf, onSet}) => {
+      store.get("site")
+        .then(site => {
+          if (site) {
+            setSelf(site as string);
+          }
+        })
+
+      onSet((newValue, _, isReset) => {
+        if (isReset || newValue === DEFAULT_SITE) {
+          store.delete("site").then(_ => {
+          });
+        } else {
+          store.set("site", newValue).then(_ => {
+          });
+        }
+      })
+    }
+  ]
+});
+
 let rateLimitInterval: number | undefined = undefined;
 export const rateLimitState = atom({
     key: "rateLimit",
@@ -1,7 +1,7 @@
 import {Button, Code, Group, Loader, Space, Stack, Switch, Text, TextInput, Tooltip} from "@mantine/core";
 import {atom, useRecoilState, useSetRecoilState} from "recoil";
 import {currentStep, maskUserState, saveToState, topicMetaState} from "../states";
-import {fetchMeta, sanitize} from "../commands";
+import {archiveDirName, fetchMeta} from "../commands";
 import debounce from "debounce-promise";
 import {useState} from "react";
 import {openConfirmModal} from "@mantine/modals";
@@ -47,7 +47,7 @@ export const Config = () => {
   const [savePath, setSavePath] = useRecoilState(savePathState);
 
   const extractTopic = (topic: string) => {
-    const [, name] = topic.match(/https:\/\/shuiyuan.sjtu.edu.cn\/t\/topic\/(\d+)/) || [];
+    const [, name] = topic.match(/\/t\/[^/]+\/(\d+)/) || [];
     return name;
   };
 
@@ -56,7 +56,7 @@ export const Config = () => {
   const onNextStep = async () => {
     console.log("readDir", await fs.readDir(savePath));
     if (await fs.exists(savePath) && (await fs.readDir(savePath)).length > 0) {
-      const filename = await sanitize(`水源_${topicMeta!.title}`);
+      const filename = await archiveDirName(topicMeta!.title);
       const isArchive = (await path.basename(savePath) === filename);
 
       const prompt = prompts[isArchive ? 1 : 0];
@@ -96,7 +96,7 @@ export const Config = () => {
   return (
     <Stack>
       <TextInput
-        label="水源 URL" error={topicError} value={topicUrl}
+        label="贴子 URL" error={topicError} value={topicUrl}
         styles={{input: {paddingRight: 96}, rightSection: {width: 92}}}
         rightSection={fetching ?
           <Group spacing={"xs"}>
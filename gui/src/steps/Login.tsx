@@ -1,6 +1,6 @@
 import {useRecoilState, useSetRecoilState} from "recoil";
-import {currentStep, tokenState} from "../states";
-import {Button, Center, Group, Loader, Space, Stack, Text, Textarea, useMantineTheme} from "@mantine/core";
+import {currentStep, siteState, tokenState} from "../states";
+import {Button, Center, Group, Loader, Space, Stack, Text, Textarea, TextInput, useMantineTheme} from "@mantine/core";
 import {useEffect, useState} from "react";
 import {loginWithToken, openBrowser, tokenFromOauth} from "../commands";
 import {listen, UnlistenFn} from "@tauri-apps/api/event";
@@ -22,6 +22,7 @@ export const Login = () => {
     const [loading, setLoading] = useState(false);
     const [opened, setOpened] = useState(OpenState.NotOpened);
     const [token, setToken] = useRecoilState(tokenState);
+    const [site, setSite] = useRecoilState(siteState);
     const setCurrentStep = useSetRecoilState(currentStep);
     const [OAuthKey, setOAuthKey] = useState("");
     const [keyError, setKeyError] = useState("");
@@ -31,7 +32,7 @@ export const Login = () => {
     const validateToken = async (token: string) => {
         setLoading(true);
         try {
-            await loginWithToken(token);
+            await loginWithToken(token, site);
             setCurrentStep(1);
         } catch (e) {
             setToken('');
@@ -83,9 +84,11 @@ export const Login = () => {
             </Center> :
             (opened === OpenState.NotOpened ?
                     <Stack pt={70}>
-                        <Text align={"center"}>为了存档水源贴子，我们需要您水源账号的只读权限</Text>
+                        <Text align={"center"}>为了存档社区贴子，我们需要您社区账号的只读权限</Text>
+                        <TextInput label="社区地址" value={site}
+                                   onChange={ev => setSite(ev.currentTarget.value)}/>
                         <Button onClick={() => {
-                            openBrowser().then((use_callback) => {
+                            openBrowser(site).then((use_callback) => {
                                 if (use_callback) {
                                     setOpened(OpenState.Callback);
                                 } else {